@@ -0,0 +1,111 @@
+use super::camera::{Angle, Camera};
+use super::vec3::{Point3, Vec3};
+
+// Values that can be blended as weighted sums, which covers both linear and
+// spline interpolation between keyframes.
+pub trait Interpolate: Copy {
+    fn weighted_sum(terms: &[(f64, Self)]) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn weighted_sum(terms: &[(f64, Self)]) -> Self {
+        terms.iter().map(|&(w, x)| w * x).sum()
+    }
+}
+
+impl Interpolate for Vec3 {
+    fn weighted_sum(terms: &[(f64, Self)]) -> Self {
+        terms.iter().map(|&(w, x)| w * x).sum()
+    }
+}
+
+impl Interpolate for Angle {
+    fn weighted_sum(terms: &[(f64, Self)]) -> Self {
+        Angle::from_radians(terms.iter().map(|&(w, x)| w * x.radians).sum())
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum Interpolation {
+    Linear,
+    // A Catmull-Rom spline through the keyframes, which eases in and out of
+    // each key without stopping.
+    CatmullRom,
+}
+
+// Values at increasing times. Before the first or after the last keyframe
+// the value holds still.
+pub struct Keyframes<T> {
+    keys: Vec<(f64, T)>,
+    interpolation: Interpolation,
+}
+
+impl<T: Interpolate> Keyframes<T> {
+    pub fn new(keys: Vec<(f64, T)>, interpolation: Interpolation) -> Self {
+        assert!(!keys.is_empty(), "animation needs a keyframe");
+        Keyframes {
+            keys,
+            interpolation,
+        }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Keyframes::new(vec![(0.0, value)], Interpolation::Linear)
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        let last = self.keys.len() - 1;
+        let i = match self.keys.iter().position(|&(t, _)| t > time) {
+            Some(0) => return self.keys[0].1,
+            None => return self.keys[last].1,
+            Some(i) => i - 1,
+        };
+        let (t1, p1) = self.keys[i];
+        let (t2, p2) = self.keys[i + 1];
+        let s = (time - t1) / (t2 - t1);
+
+        match self.interpolation {
+            Interpolation::Linear => T::weighted_sum(&[(1.0 - s, p1), (s, p2)]),
+            Interpolation::CatmullRom => {
+                let p0 = self.keys[i.saturating_sub(1)].1;
+                let p3 = self.keys[(i + 2).min(last)].1;
+                let (s2, s3) = (s * s, s * s * s);
+                T::weighted_sum(&[
+                    (0.5 * (-s3 + 2.0 * s2 - s), p0),
+                    (0.5 * (3.0 * s3 - 5.0 * s2 + 2.0), p1),
+                    (0.5 * (-3.0 * s3 + 4.0 * s2 + s), p2),
+                    (0.5 * (s3 - s2), p3),
+                ])
+            }
+        }
+    }
+}
+
+// A camera moving over time. The focus stays on `look_at`.
+pub struct CameraPath {
+    pub look_from: Keyframes<Point3>,
+    pub look_at: Keyframes<Point3>,
+    pub vfov: Keyframes<Angle>,
+    pub vup: Vec3,
+    pub aspect_ratio: f64,
+    pub aperture: f64,
+}
+
+impl CameraPath {
+    // The camera for a frame starting at `time`, with the shutter open for
+    // `shutter` seconds after it.
+    pub fn camera(&self, time: f64, shutter: f64) -> Camera {
+        let look_from = self.look_from.at(time);
+        let look_at = self.look_at.at(time);
+        Camera::new(
+            look_from,
+            look_at,
+            self.vup,
+            self.vfov.at(time),
+            self.aspect_ratio,
+            self.aperture,
+            (look_from - look_at).length(),
+        )
+        .with_shutter(time, time + shutter)
+    }
+}
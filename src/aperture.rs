@@ -0,0 +1,152 @@
+use super::camera::Angle;
+use super::grid::invalid_data;
+use super::random::random_in_unit_disk;
+use super::vec3::Vec3;
+use rand::{rngs::SmallRng, Rng};
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+// The shape of the opening in a lens, which is the shape out of focus
+// highlights take. Circles and polygons fit in the unit disk, and masks fill
+// the square around it.
+#[derive(Clone)]
+pub enum Aperture {
+    Circle,
+    // A regular polygon, as formed by the blades of an iris. Build it with
+    // `Aperture::polygon`, which checks there are enough blades.
+    Polygon { blades: u32, rotation: Angle },
+    Mask(Arc<ApertureMask>),
+}
+
+impl Aperture {
+    pub fn polygon(blades: u32, rotation: Angle) -> Self {
+        assert!(blades >= 3, "an iris needs at least 3 blades");
+        Aperture::Polygon { blades, rotation }
+    }
+
+    // A point spread evenly over the aperture, in the xy plane.
+    pub fn sample(&self, rng: &mut SmallRng) -> Vec3 {
+        match self {
+            Aperture::Circle => random_in_unit_disk(rng),
+            Aperture::Polygon { blades, rotation } => {
+                // Pick one of the triangles between the center and an edge,
+                // then a point in it.
+                let step = 2.0 * PI / *blades as f64;
+                let edge = rng.gen_range(0, *blades) as f64;
+                let corner = |i: f64| {
+                    let angle = rotation.radians + i * step;
+                    Vec3::new(angle.cos(), angle.sin(), 0.0)
+                };
+                let (a, b) = (corner(edge), corner(edge + 1.0));
+                let (x, y) = (rng.gen::<f64>().sqrt(), rng.gen::<f64>());
+                x * ((1.0 - y) * a + y * b)
+            }
+            Aperture::Mask(mask) => mask.sample(rng),
+        }
+    }
+}
+
+// An image of the aperture, with white where light gets through. It is
+// stretched over the square around the unit disk.
+pub struct ApertureMask {
+    width: usize,
+    height: usize,
+    values: Vec<f64>,
+    max: f64,
+}
+
+impl ApertureMask {
+    // Loads a plain (P2 or P3) PGM or PPM image. Colour images use the mean of
+    // their channels.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut tokens = text
+            .lines()
+            .map(|line| line.split('#').next().unwrap())
+            .flat_map(|line| line.split_whitespace());
+
+        let channels = match tokens.next() {
+            Some("P2") => 1,
+            Some("P3") => 3,
+            _ => return Err(invalid_data("aperture mask must be a plain PGM or PPM")),
+        };
+        let mut values = tokens.map(|token| {
+            token
+                .parse::<f64>()
+                .map_err(|_| invalid_data("aperture mask contains a non-numeric value"))
+        });
+        let mut next = || {
+            values
+                .next()
+                .unwrap_or_else(|| Err(invalid_data("aperture mask is truncated")))
+        };
+
+        let width = next()? as usize;
+        let height = next()? as usize;
+        let max = next()?;
+        let mut pixels = Vec::with_capacity(width * height);
+        for _ in 0..width * height {
+            let mut sum = 0.0;
+            for _ in 0..channels {
+                sum += next()?;
+            }
+            pixels.push(sum / (channels as f64 * max));
+        }
+        let max = pixels.iter().cloned().fold(0.0, f64::max);
+        if max <= 0.0 {
+            return Err(invalid_data("aperture mask is black"));
+        }
+
+        Ok(ApertureMask {
+            width,
+            height,
+            values: pixels,
+            max,
+        })
+    }
+
+    // Rejection sampling, keeping points in proportion to the mask's
+    // brightness.
+    fn sample(&self, rng: &mut SmallRng) -> Vec3 {
+        loop {
+            let (x, y) = (rng.gen::<f64>(), rng.gen::<f64>());
+            let column = ((x * self.width as f64) as usize).min(self.width - 1);
+            let row = (((1.0 - y) * self.height as f64) as usize).min(self.height - 1);
+            if rng.gen::<f64>() * self.max < self.values[row * self.width + column] {
+                return Vec3::new(2.0 * x - 1.0, 2.0 * y - 1.0, 0.0);
+            }
+        }
+    }
+}
+
+// The physical settings of a camera lens, in millimetres, for a scene
+// measured in metres.
+#[derive(Copy, Clone)]
+pub struct Optics {
+    pub focal_length: f64,
+    pub f_stop: f64,
+    pub sensor_height: f64,
+}
+
+impl Optics {
+    // A lens on a 36 x 24 mm full frame sensor.
+    pub fn full_frame(focal_length: f64, f_stop: f64) -> Self {
+        Optics {
+            focal_length,
+            f_stop,
+            sensor_height: 24.0,
+        }
+    }
+
+    pub fn vfov(&self) -> Angle {
+        Angle::from_radians(2.0 * (self.sensor_height / (2.0 * self.focal_length)).atan())
+    }
+
+    // The diameter of the entrance pupil, in metres.
+    pub fn aperture(&self) -> f64 {
+        self.focal_length / self.f_stop / 1000.0
+    }
+}
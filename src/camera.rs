@@ -1,10 +1,112 @@
-use super::random::random_in_unit_disk;
+use super::aperture::{Aperture, Optics};
+use super::matrix::Matrix4;
 use super::vec3::{CrossProduct, Point3, Ray, UnitVec3, Vec3};
-use rand::Rng;
+use rand::{rngs::SmallRng, Rng};
 use std::f64::consts::PI;
 use std::ops::Div;
 
+const MAX_APERTURE_TRIES: usize = 4096;
+
+// A way of turning points on the image into rays. `s` and `t` run from 0 to 1
+// across and up the image. Returns None for points the projection doesn't
+// cover, which render black.
+pub trait Projection: Sync {
+    fn get_ray(&self, rng: &mut SmallRng, s: f64, t: f64) -> Option<Ray>;
+
+    // A ray with the factor to scale the light it brings back by, for
+    // projections that don't send rays in proportion to the light each
+    // gathers.
+    fn get_weighted_ray(&self, rng: &mut SmallRng, s: f64, t: f64) -> Option<(Ray, f64)> {
+        self.get_ray(rng, s, t).map(|ray| (ray, 1.0))
+    }
+}
+
+// Projections that can be moved sideways to make stereo pairs.
+pub trait Stereo: Projection + Sized {
+    // The same view seen from `offset` to the right, with the two views of a
+    // stereo pair lining up at `convergence` in front of the camera.
+    fn shifted(&self, offset: f64, convergence: f64) -> Self;
+}
+
+// Views for the left and right eyes, `interocular` apart. Objects at
+// `convergence` from the camera appear at the depth of the screen.
+pub struct StereoPair<P> {
+    pub left: P,
+    pub right: P,
+}
+
+impl<P: Stereo> StereoPair<P> {
+    pub fn new(center: &P, interocular: f64, convergence: f64) -> Self {
+        StereoPair {
+            left: center.shifted(-interocular / 2.0, convergence),
+            right: center.shifted(interocular / 2.0, convergence),
+        }
+    }
+}
+
+// The interval over which rays are sent, with times spread evenly over it.
+#[derive(Copy, Clone, Default)]
+pub struct Shutter {
+    pub open: f64,
+    pub close: f64,
+}
+
+impl Shutter {
+    pub fn sample(&self, rng: &mut SmallRng) -> f64 {
+        self.open + rng.gen::<f64>() * (self.close - self.open)
+    }
+}
+
+#[derive(Copy, Clone)]
+enum Mode {
+    Perspective,
+    // Parallel rays along `direction`, starting on the plane of the image.
+    Orthographic { direction: UnitVec3 },
+}
+
+// Brown-Conrady lens distortion, with radial terms `k1` to `k3` and
+// tangential terms `p1` and `p2`, as used by common camera calibration tools.
+// It maps undistorted image positions, in units of the distance to the image
+// plane, to where the lens puts them.
+#[derive(Copy, Clone, Default)]
+pub struct Distortion {
+    pub k1: f64,
+    pub k2: f64,
+    pub k3: f64,
+    pub p1: f64,
+    pub p2: f64,
+}
+
+impl Distortion {
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let r2 = x * x + y * y;
+        let radial = 1.0 + r2 * (self.k1 + r2 * (self.k2 + r2 * self.k3));
+        (
+            x * radial + 2.0 * self.p1 * x * y + self.p2 * (r2 + 2.0 * x * x),
+            y * radial + self.p1 * (r2 + 2.0 * y * y) + 2.0 * self.p2 * x * y,
+        )
+    }
+
+    // The undistorted position that `apply` takes to (x, y), by fixed point
+    // iteration. Returns None where there isn't one, as in the corners of
+    // images with strong barrel distortion.
+    pub fn remove(&self, x: f64, y: f64) -> Option<(f64, f64)> {
+        let (mut ux, mut uy) = (x, y);
+        for _ in 0..50 {
+            let (dx, dy) = self.apply(ux, uy);
+            if (x - dx).abs() < 1e-9 && (y - dy).abs() < 1e-9 {
+                return Some((ux, uy));
+            }
+            ux += x - dx;
+            uy += y - dy;
+        }
+        None
+    }
+}
+
+#[derive(Clone)]
 pub struct Camera {
+    mode: Mode,
     origin: Point3,
     lower_left_corner: Point3,
     horizontal: Vec3,
@@ -12,6 +114,12 @@ pub struct Camera {
     u: UnitVec3,
     v: UnitVec3,
     lens_radius: f64,
+    aperture: Aperture,
+    cat_eye: f64,
+    focus_distance: f64,
+    focal_plane: Option<UnitVec3>,
+    distortion: Option<Distortion>,
+    shutter: Shutter,
 }
 
 #[derive(Copy, Clone, Add, Neg, Sub)]
@@ -31,7 +139,7 @@ impl Angle {
     }
 
     pub fn from_radians(radians: f64) -> Self {
-        Angle { radians: radians }
+        Angle { radians }
     }
 
     pub fn tan(&self) -> f64 {
@@ -84,6 +192,7 @@ impl Camera {
         let lens_radius = aperture / 2.0;
 
         Self {
+            mode: Mode::Perspective,
             origin,
             lower_left_corner,
             horizontal,
@@ -91,18 +200,203 @@ impl Camera {
             u,
             v,
             lens_radius,
+            aperture: Aperture::Circle,
+            cat_eye: 0.0,
+            focus_distance,
+            focal_plane: None,
+            distortion: None,
+            shutter: Shutter::default(),
         }
     }
 
-    pub fn get_ray<R: Rng>(&self, rng: &mut R, s: f64, t: f64) -> Ray {
-        let rd = self.lens_radius * random_in_unit_disk(rng);
-        let offset = self.u * rd.x + self.v * rd.y;
-        Ray::new(
-            self.origin + offset,
-            (self.lower_left_corner + s * self.horizontal + t * self.vertical
-                - self.origin
-                - offset)
-                .unit_vector(),
+    // A camera with the field of view and aperture of a physical lens, in a
+    // scene measured in metres.
+    pub fn from_optics(
+        look_from: Point3,
+        look_at: Point3,
+        vup: Vec3,
+        optics: &Optics,
+        aspect_ratio: f64,
+        focus_distance: f64,
+    ) -> Self {
+        Camera::new(
+            look_from,
+            look_at,
+            vup,
+            optics.vfov(),
+            aspect_ratio,
+            optics.aperture(),
+            focus_distance,
         )
     }
+
+    // A camera with parallel rays, showing a view `view_width` across with no
+    // perspective or depth of field.
+    pub fn orthographic(
+        look_from: Point3,
+        look_at: Point3,
+        vup: Vec3,
+        view_width: f64,
+        aspect_ratio: f64,
+    ) -> Self {
+        let w = (look_from - look_at).unit_vector();
+        let u = vup.cross(w).unit_vector();
+        let v = w.cross(u);
+
+        let horizontal = view_width * u;
+        let vertical = (view_width / aspect_ratio) * v;
+
+        Self {
+            mode: Mode::Orthographic { direction: -w },
+            origin: look_from,
+            lower_left_corner: look_from - (horizontal + vertical) / 2.0,
+            horizontal,
+            vertical,
+            u,
+            v,
+            lens_radius: 0.0,
+            aperture: Aperture::Circle,
+            cat_eye: 0.0,
+            focus_distance: 0.0,
+            focal_plane: None,
+            distortion: None,
+            shutter: Shutter::default(),
+        }
+    }
+
+    pub fn with_aperture(self, aperture: Aperture) -> Self {
+        Camera { aperture, ..self }
+    }
+
+    // Cat-eye vignetting: towards the edges of the image the lens barrel cuts
+    // off one side of the aperture, by up to `cat_eye` of its radius in the
+    // corners, giving out of focus highlights a lemon shape.
+    pub fn with_cat_eye(self, cat_eye: f64) -> Self {
+        Camera { cat_eye, ..self }
+    }
+
+    // Lens shift: moves the image across the image plane by fractions of its
+    // width and height, without turning the camera, so that vertical lines
+    // stay parallel when looking up at a building.
+    pub fn with_shift(self, x: f64, y: f64) -> Self {
+        Camera {
+            lower_left_corner: self.lower_left_corner + x * self.horizontal + y * self.vertical,
+            ..self
+        }
+    }
+
+    // Tilts the plane of focus away from the image plane, as a tilted lens
+    // does by the Scheimpflug principle. `tilt` turns it about the camera's
+    // horizontal axis and `swing` about its vertical axis. It still passes
+    // through the point in focus straight ahead.
+    pub fn with_tilt(self, tilt: Angle, swing: Angle) -> Self {
+        let w: Vec3 = self.u.cross(self.v).into();
+        let normal =
+            Matrix4::rotation(self.v.into(), swing) * Matrix4::rotation(self.u.into(), tilt);
+        Camera {
+            focal_plane: Some(normal.transform_vector(w).unit_vector()),
+            ..self
+        }
+    }
+
+    pub fn with_distortion(self, distortion: Distortion) -> Self {
+        Camera {
+            distortion: Some(distortion),
+            ..self
+        }
+    }
+
+    // The point where the camera's axis meets the image plane, and the
+    // distance to it.
+    fn axis(&self) -> (Point3, f64) {
+        match self.mode {
+            Mode::Perspective => {
+                let w = self.u.cross(self.v);
+                (self.origin - self.focus_distance * w, self.focus_distance)
+            }
+            Mode::Orthographic { .. } => (self.origin, 1.0),
+        }
+    }
+
+    // The point on the image plane that the lens shows at (s, t).
+    fn target(&self, s: f64, t: f64) -> Option<Point3> {
+        let target = self.lower_left_corner + s * self.horizontal + t * self.vertical;
+        let distortion = match self.distortion {
+            Some(distortion) => distortion,
+            None => return Some(target),
+        };
+        let (center, distance) = self.axis();
+        let offset = (target - center) / distance;
+        let (x, y) = distortion.remove(
+            Vec3::dot(offset, self.u.into()),
+            Vec3::dot(offset, self.v.into()),
+        )?;
+        Some(center + distance * (self.u * x + self.v * y))
+    }
+
+    // A point on the part of the aperture the barrel leaves open, by
+    // rejection. Returns None if nothing is found, as when the barrel covers
+    // all of a mask's opening.
+    fn sample_aperture(&self, rng: &mut SmallRng, s: f64, t: f64) -> Option<Vec3> {
+        let corner = Vec3::new(1.0, 1.0, 0.0).length();
+        let clip = (self.cat_eye / corner) * Vec3::new(2.0 * s - 1.0, 2.0 * t - 1.0, 0.0);
+        (0..MAX_APERTURE_TRIES)
+            .map(|_| self.aperture.sample(rng))
+            .find(|p| (*p - clip).length_squared() <= 1.0)
+    }
+
+    // Rays are sent at times spread evenly over the shutter interval.
+    pub fn with_shutter(self, open: f64, close: f64) -> Self {
+        Camera {
+            shutter: Shutter { open, close },
+            ..self
+        }
+    }
+}
+
+// The image is shifted rather than the camera turned, so the two views show
+// no vertical parallax.
+impl Stereo for Camera {
+    fn shifted(&self, offset: f64, convergence: f64) -> Self {
+        let shift = offset * (1.0 - self.focus_distance / convergence);
+        Camera {
+            origin: self.origin + self.u * offset,
+            lower_left_corner: self.lower_left_corner + self.u * shift,
+            ..self.clone()
+        }
+    }
+}
+
+impl Projection for Camera {
+    fn get_ray(&self, rng: &mut SmallRng, s: f64, t: f64) -> Option<Ray> {
+        let target = self.target(s, t)?;
+        let ray = match self.mode {
+            Mode::Perspective => {
+                // Rays through the lens meet where the ray through its center
+                // crosses the plane of focus, if it does.
+                let target = match self.focal_plane {
+                    Some(normal) => {
+                        let (center, _) = self.axis();
+                        let direction = target - self.origin;
+                        let n: Vec3 = normal.into();
+                        let along = Vec3::dot(center - self.origin, n) / Vec3::dot(direction, n);
+                        if along.is_finite() && along > 0.0 {
+                            self.origin + along * direction
+                        } else {
+                            target
+                        }
+                    }
+                    None => target,
+                };
+                let rd = self.lens_radius * self.sample_aperture(rng, s, t)?;
+                let offset = self.u * rd.x + self.v * rd.y;
+                Ray::new(
+                    self.origin + offset,
+                    (target - self.origin - offset).unit_vector(),
+                )
+            }
+            Mode::Orthographic { direction } => Ray::new(target, direction),
+        };
+        Some(ray.with_time(self.shutter.sample(rng)))
+    }
 }
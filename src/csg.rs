@@ -0,0 +1,107 @@
+use super::hittable::{Hit, Hittable, Interval, Solid};
+use super::material::Material;
+use super::vec3::Ray;
+use super::world::Object;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Operation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl Operation {
+    fn inside(self, left: bool, right: bool) -> bool {
+        match self {
+            Operation::Union => left || right,
+            Operation::Intersection => left && right,
+            Operation::Difference => left && !right,
+        }
+    }
+}
+
+// Constructive solid geometry: combines two solids by sweeping along the
+// ray through both sets of intervals. Surfaces of the right hand solid that
+// bound a difference face inwards, so their normals are flipped. The whole
+// result uses one material.
+pub struct Csg {
+    pub operation: Operation,
+    pub left: Box<dyn Solid>,
+    pub right: Box<dyn Solid>,
+    pub material: Material,
+}
+
+impl Csg {
+    pub fn new<L, R>(operation: Operation, left: L, right: R, material: &Material) -> Self
+    where
+        L: Solid + 'static,
+        R: Solid + 'static,
+    {
+        Csg {
+            operation,
+            left: Box::new(left),
+            right: Box::new(right),
+            material: material.clone(),
+        }
+    }
+}
+
+impl Solid for Csg {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let mut events = vec![];
+        for (is_left, solid) in &[(true, &self.left), (false, &self.right)] {
+            for interval in solid.intervals(ray) {
+                events.push((interval.enter, *is_left, true));
+                events.push((interval.exit, *is_left, false));
+            }
+        }
+        events.sort_by(|a, b| a.0.t.partial_cmp(&b.0.t).unwrap());
+
+        let (mut in_left, mut in_right, mut inside) = (false, false, false);
+        let mut enter = None;
+        let mut result = vec![];
+        for (crossing, is_left, entering) in events {
+            if is_left {
+                in_left = entering;
+            } else {
+                in_right = entering;
+            }
+
+            let now_inside = self.operation.inside(in_left, in_right);
+            if now_inside == inside {
+                continue;
+            }
+            inside = now_inside;
+
+            let crossing = if !is_left && self.operation == Operation::Difference {
+                crossing.flipped()
+            } else {
+                crossing
+            };
+            match enter.take() {
+                None => enter = Some(crossing),
+                Some(enter) => result.push(Interval {
+                    enter,
+                    exit: crossing,
+                }),
+            }
+        }
+        result
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        self.intervals(ray)
+            .iter()
+            .flat_map(|interval| vec![interval.enter, interval.exit])
+            .find(|crossing| crossing.t >= t_min && crossing.t <= t_max)
+            .map(|crossing| crossing.to_hit(ray))
+    }
+}
+
+impl Object for Csg {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
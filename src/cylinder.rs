@@ -0,0 +1,232 @@
+use super::hittable::{Hit, Hittable};
+use super::material::Material;
+use super::vec3::{Point3, Ray, Vec3};
+use super::world::Object;
+use std::f64::consts::PI;
+
+// Cylinders, cones and capsules are built upright along the y axis, starting
+// at `base` and rising to `base.y + height`; wrap them in `Transformed` to
+// orient them. UV coordinates run around the axis and up the side, with caps
+// mapped across their bounding square.
+
+fn quadratic(a: f64, half_b: f64, c: f64) -> Option<[f64; 2]> {
+    if a.abs() < 1e-12 {
+        if half_b.abs() < 1e-12 {
+            return None;
+        }
+        let t = -c / (2.0 * half_b);
+        return Some([t, t]);
+    }
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrtd = discriminant.sqrt();
+    let (t0, t1) = ((-half_b - sqrtd) / a, (-half_b + sqrtd) / a);
+    Some(if t0 < t1 { [t0, t1] } else { [t1, t0] })
+}
+
+fn around_axis(local: Vec3) -> f64 {
+    ((-local.z).atan2(local.x) + PI) / (2.0 * PI)
+}
+
+fn cap(origin: Vec3, direction: Vec3, y: f64, radius: f64) -> Option<(f64, (f64, f64))> {
+    if direction.y.abs() < 1e-12 || radius <= 0.0 {
+        return None;
+    }
+    let t = (y - origin.y) / direction.y;
+    let p = origin + t * direction;
+    if p.x * p.x + p.z * p.z > radius * radius {
+        return None;
+    }
+    Some((t, (0.5 * (p.x / radius + 1.0), 0.5 * (p.z / radius + 1.0))))
+}
+
+// Keeps the nearest of several candidate intersections.
+struct Nearest {
+    t_min: f64,
+    t_max: f64,
+    best: Option<(f64, Vec3, (f64, f64))>,
+}
+
+impl Nearest {
+    fn new(t_min: f64, t_max: f64) -> Self {
+        Nearest {
+            t_min,
+            t_max,
+            best: None,
+        }
+    }
+
+    fn consider(&mut self, t: f64, normal: Vec3, uv: (f64, f64)) {
+        if t >= self.t_min && t <= self.t_max {
+            self.t_max = t;
+            self.best = Some((t, normal, uv));
+        }
+    }
+
+    fn hit(self, ray: &Ray) -> Option<Hit> {
+        let (t, normal, uv) = self.best?;
+        Some(Hit::new(t, ray, ray.at(t), normal.unit_vector(), uv))
+    }
+}
+
+pub struct Cylinder {
+    pub base: Point3,
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    pub material: Material,
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let o = ray.origin - self.base;
+        let d: Vec3 = ray.direction.into();
+        let mut nearest = Nearest::new(t_min, t_max);
+
+        let a = d.x * d.x + d.z * d.z;
+        let half_b = o.x * d.x + o.z * d.z;
+        let c = o.x * o.x + o.z * o.z - self.radius * self.radius;
+        if let Some(roots) = quadratic(a, half_b, c) {
+            for &t in &roots {
+                let p = o + t * d;
+                if p.y >= 0.0 && p.y <= self.height {
+                    let normal = Vec3::new(p.x, 0.0, p.z);
+                    nearest.consider(t, normal, (around_axis(p), p.y / self.height));
+                }
+            }
+        }
+
+        if self.capped {
+            for &(y, normal_y) in &[(0.0, -1.0), (self.height, 1.0)] {
+                if let Some((t, uv)) = cap(o, d, y, self.radius) {
+                    nearest.consider(t, Vec3::new(0.0, normal_y, 0.0), uv);
+                }
+            }
+        }
+
+        nearest.hit(ray)
+    }
+}
+
+impl Object for Cylinder {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+// A cone, or a frustum when both radii are non-zero.
+pub struct Cone {
+    pub base: Point3,
+    pub base_radius: f64,
+    pub top_radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    pub material: Material,
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let o = ray.origin - self.base;
+        let d: Vec3 = ray.direction.into();
+        let mut nearest = Nearest::new(t_min, t_max);
+
+        // The radius at height y is r0 + k y.
+        let r0 = self.base_radius;
+        let k = (self.top_radius - self.base_radius) / self.height;
+        let radius_at_origin = r0 + k * o.y;
+
+        let a = d.x * d.x + d.z * d.z - k * k * d.y * d.y;
+        let half_b = o.x * d.x + o.z * d.z - k * d.y * radius_at_origin;
+        let c = o.x * o.x + o.z * o.z - radius_at_origin * radius_at_origin;
+        if let Some(roots) = quadratic(a, half_b, c) {
+            for &t in &roots {
+                let p = o + t * d;
+                if p.y >= 0.0 && p.y <= self.height {
+                    let normal = Vec3::new(p.x, -k * (r0 + k * p.y), p.z);
+                    nearest.consider(t, normal, (around_axis(p), p.y / self.height));
+                }
+            }
+        }
+
+        if self.capped {
+            let caps = [
+                (0.0, -1.0, self.base_radius),
+                (self.height, 1.0, self.top_radius),
+            ];
+            for &(y, normal_y, radius) in &caps {
+                if let Some((t, uv)) = cap(o, d, y, radius) {
+                    nearest.consider(t, Vec3::new(0.0, normal_y, 0.0), uv);
+                }
+            }
+        }
+
+        nearest.hit(ray)
+    }
+}
+
+impl Object for Cone {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+// A cylinder of `height` closed by hemispheres, so the full shape extends
+// `radius` beyond each end.
+pub struct Capsule {
+    pub base: Point3,
+    pub radius: f64,
+    pub height: f64,
+    pub material: Material,
+}
+
+impl Hittable for Capsule {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let o = ray.origin - self.base;
+        let d: Vec3 = ray.direction.into();
+        let r = self.radius;
+        let total = self.height + 2.0 * r;
+        let mut nearest = Nearest::new(t_min, t_max);
+
+        let a = d.x * d.x + d.z * d.z;
+        let half_b = o.x * d.x + o.z * d.z;
+        let c = o.x * o.x + o.z * o.z - r * r;
+        if let Some(roots) = quadratic(a, half_b, c) {
+            for &t in &roots {
+                let p = o + t * d;
+                if p.y >= 0.0 && p.y <= self.height {
+                    let normal = Vec3::new(p.x, 0.0, p.z);
+                    nearest.consider(t, normal, (around_axis(p), (p.y + r) / total));
+                }
+            }
+        }
+
+        let ends = [
+            (Vec3::new(0.0, 0.0, 0.0), -1.0),
+            (Vec3::new(0.0, self.height, 0.0), 1.0),
+        ];
+        for &(center, side) in &ends {
+            let oc = o - center;
+            let half_b = Vec3::dot(oc, d);
+            let c = oc.length_squared() - r * r;
+            if let Some(roots) = quadratic(1.0, half_b, c) {
+                for &t in &roots {
+                    let normal = oc + t * d;
+                    if normal.y * side >= 0.0 {
+                        let p = o + t * d;
+                        nearest.consider(t, normal, (around_axis(p), (p.y + r) / total));
+                    }
+                }
+            }
+        }
+
+        nearest.hit(ray)
+    }
+}
+
+impl Object for Capsule {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
@@ -0,0 +1,172 @@
+use super::spectrum::{blackbody_xyz, xyz_to_linear_srgb, xyz_to_rgb};
+use super::vec3::{Color, Vec3};
+use std::ops::Range;
+
+type Matrix3 = [[f64; 3]; 3];
+
+const SRGB_TO_XYZ: Matrix3 = [
+    [0.412_456_4, 0.357_576_1, 0.180_437_5],
+    [0.212_672_9, 0.715_152_2, 0.072_175_0],
+    [0.019_333_9, 0.119_192_0, 0.950_304_1],
+];
+
+// Bradford cone response, for adapting colours from one white to another.
+const BRADFORD: Matrix3 = [
+    [0.895_1, 0.266_4, -0.161_4],
+    [-0.750_2, 1.713_5, 0.036_7],
+    [0.038_9, -0.068_5, 1.029_6],
+];
+
+const BRADFORD_INVERSE: Matrix3 = [
+    [0.986_992_9, -0.147_054_3, 0.159_962_7],
+    [0.432_305_3, 0.518_360_3, 0.049_291_2],
+    [-0.008_528_7, 0.040_042_8, 0.968_486_7],
+];
+
+fn apply(m: &Matrix3, v: Vec3) -> Vec3 {
+    Vec3::new(
+        m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+        m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+        m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+    )
+}
+
+// Exposure value at ISO 100 for an f-number, a shutter time in seconds and
+// an ISO sensitivity.
+pub fn ev100(f_number: f64, shutter: f64, iso: f64) -> f64 {
+    (f_number * f_number / shutter * 100.0 / iso).log2()
+}
+
+// The image a camera records: its resolution in pixels, the shape of the
+// pixels, and the part of the frame to render.
+//
+// The film also turns the radiance reaching the camera into the linear
+// colour of the image, before tone mapping. Scene radiance is taken to be in
+// cd/m^2, so `exposure` is the scale for an exposure value by the standard
+// saturation based sensitivity. With no settings the radiance is passed
+// through unchanged.
+pub struct Film {
+    pub width: i64,
+    pub height: i64,
+    // Width over height of each pixel.
+    pub pixel_aspect: f64,
+    // The rendered part of the frame, as fractions of its width and height
+    // from the top left corner.
+    pub crop_min: (f64, f64),
+    pub crop_max: (f64, f64),
+    pub exposure: f64,
+    pub white_balance: Option<Matrix3>,
+}
+
+impl Film {
+    pub fn new(width: i64, height: i64) -> Self {
+        Film {
+            width,
+            height,
+            pixel_aspect: 1.0,
+            crop_min: (0.0, 0.0),
+            crop_max: (1.0, 1.0),
+            exposure: 1.0,
+            white_balance: None,
+        }
+    }
+
+    // The aspect ratio of the whole frame as it will be displayed, which
+    // cameras should be given.
+    pub fn aspect_ratio(&self) -> f64 {
+        self.width as f64 * self.pixel_aspect / self.height as f64
+    }
+
+    pub fn with_pixel_aspect(self, pixel_aspect: f64) -> Self {
+        Film {
+            pixel_aspect,
+            ..self
+        }
+    }
+
+    // Returns None for a window with nothing inside the frame.
+    pub fn with_crop(self, (x0, x1): (f64, f64), (y0, y1): (f64, f64)) -> Option<Self> {
+        let crop_min = (x0.max(0.0), y0.max(0.0));
+        let crop_max = (x1.min(1.0), y1.min(1.0));
+        if !(crop_min.0 < crop_max.0 && crop_min.1 < crop_max.1) {
+            return None;
+        }
+        Some(Film {
+            crop_min,
+            crop_max,
+            ..self
+        })
+    }
+
+    // The columns of pixels at least partly inside the crop window.
+    pub fn columns(&self) -> Range<i64> {
+        let width = self.width as f64;
+        (self.crop_min.0 * width).floor() as i64..(self.crop_max.0 * width).ceil() as i64
+    }
+
+    // The rows of pixels at least partly inside the crop window, counted from
+    // the top.
+    pub fn rows(&self) -> Range<i64> {
+        let height = self.height as f64;
+        (self.crop_min.1 * height).floor() as i64..(self.crop_max.1 * height).ceil() as i64
+    }
+
+    pub fn with_ev100(self, ev100: f64) -> Self {
+        Film {
+            exposure: 1.0 / (1.2 * 2f64.powf(ev100)),
+            ..self
+        }
+    }
+
+    pub fn with_camera_settings(self, f_number: f64, shutter: f64, iso: f64) -> Self {
+        self.with_ev100(ev100(f_number, shutter, iso))
+    }
+
+    // Makes light from a black body at `temperature` kelvin look white, by a
+    // Bradford chromatic adaptation to the sRGB white point. Returns None for
+    // bodies too cool to give off light that can be balanced.
+    pub fn with_white_balance(self, temperature: f64) -> Option<Self> {
+        // The colour as rendered, before it is clipped to the RGB gamut.
+        let light = apply(&SRGB_TO_XYZ, xyz_to_rgb(blackbody_xyz(temperature)));
+        let source = apply(&BRADFORD, light / light.y);
+        if !(source.x > 0.0 && source.y > 0.0 && source.z > 0.0) {
+            return None;
+        }
+        let target = apply(&BRADFORD, apply(&SRGB_TO_XYZ, Color::new(1.0, 1.0, 1.0)));
+        let scale = [
+            target.x / source.x,
+            target.y / source.y,
+            target.z / source.z,
+        ];
+
+        // Columns of the adaptation are the images of the sRGB primaries.
+        let mut m = [[0.0; 3]; 3];
+        for (j, primary) in [
+            Color::new(1.0, 0.0, 0.0),
+            Color::new(0.0, 1.0, 0.0),
+            Color::new(0.0, 0.0, 1.0),
+        ]
+        .iter()
+        .enumerate()
+        {
+            let cone = apply(&BRADFORD, apply(&SRGB_TO_XYZ, *primary));
+            let adapted = Vec3::new(cone.x * scale[0], cone.y * scale[1], cone.z * scale[2]);
+            let rgb = xyz_to_linear_srgb(apply(&BRADFORD_INVERSE, adapted));
+            for (i, row) in m.iter_mut().enumerate() {
+                row[j] = [rgb.x, rgb.y, rgb.z][i];
+            }
+        }
+        Some(Film {
+            white_balance: Some(m),
+            ..self
+        })
+    }
+
+    pub fn develop(&self, radiance: Color) -> Color {
+        let color = self.exposure * radiance;
+        match &self.white_balance {
+            Some(m) => apply(m, color),
+            None => color,
+        }
+    }
+}
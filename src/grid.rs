@@ -0,0 +1,260 @@
+use super::medium::{HenyeyGreenstein, Medium, MediumSample};
+use super::spectrum::{at_wavelength, blackbody_rgb};
+use super::vec3::{Color, Point3, Ray, Vec3};
+use rand::{rngs::SmallRng, Rng};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// A dense grid of voxel values, stored with x varying fastest.
+pub struct VoxelGrid {
+    pub nx: usize,
+    pub ny: usize,
+    pub nz: usize,
+    pub density: Vec<f64>,
+    pub temperature: Option<Vec<f64>>,
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+impl VoxelGrid {
+    // Loads a whitespace separated text grid. The header is `nx ny nz
+    // channels`, followed by one line per voxel with x varying fastest. Each
+    // line holds a density, and a temperature in kelvin when channels is 2.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut tokens = text.split_whitespace();
+        let mut size = || {
+            tokens
+                .next()
+                .and_then(|token| token.parse::<usize>().ok())
+                .filter(|&n| n > 0)
+                .ok_or_else(|| invalid_data("voxel grid header needs four positive integers"))
+        };
+        let nx = size()?;
+        let ny = size()?;
+        let nz = size()?;
+        let channels = size()?;
+        if channels != 1 && channels != 2 {
+            return Err(invalid_data("voxel grid must have 1 or 2 channels"));
+        }
+
+        let mut values = tokens.map(|token| {
+            token
+                .parse::<f64>()
+                .map_err(|_| invalid_data("voxel grid contains a non-numeric value"))
+        });
+        let mut next = || {
+            values
+                .next()
+                .unwrap_or_else(|| Err(invalid_data("voxel grid is truncated")))
+        };
+
+        let count = nx * ny * nz;
+        let mut density = Vec::with_capacity(count);
+        let mut temperature = Vec::with_capacity(if channels == 2 { count } else { 0 });
+        for _ in 0..count {
+            density.push(next()?);
+            if channels == 2 {
+                temperature.push(next()?);
+            }
+        }
+
+        Ok(VoxelGrid {
+            nx,
+            ny,
+            nz,
+            density,
+            temperature: if channels == 2 {
+                Some(temperature)
+            } else {
+                None
+            },
+        })
+    }
+
+    fn max_density(&self) -> f64 {
+        self.density.iter().cloned().fold(0.0, f64::max)
+    }
+
+    // Trilinear interpolation at `p` in grid coordinates, where voxel centres
+    // lie at integer positions.
+    fn lookup(&self, values: &[f64], p: Vec3) -> f64 {
+        let clamp = |x: f64, n: usize| x.max(0.0).min((n - 1) as f64);
+        let x = clamp(p.x, self.nx);
+        let y = clamp(p.y, self.ny);
+        let z = clamp(p.z, self.nz);
+
+        let (x0, y0, z0) = (x as usize, y as usize, z as usize);
+        let x1 = (x0 + 1).min(self.nx - 1);
+        let y1 = (y0 + 1).min(self.ny - 1);
+        let z1 = (z0 + 1).min(self.nz - 1);
+        let (fx, fy, fz) = (x - x0 as f64, y - y0 as f64, z - z0 as f64);
+
+        let value = |i: usize, j: usize, k: usize| values[i + self.nx * (j + self.ny * k)];
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+
+        lerp(
+            lerp(
+                lerp(value(x0, y0, z0), value(x1, y0, z0), fx),
+                lerp(value(x0, y1, z0), value(x1, y1, z0), fx),
+                fy,
+            ),
+            lerp(
+                lerp(value(x0, y0, z1), value(x1, y0, z1), fx),
+                lerp(value(x0, y1, z1), value(x1, y1, z1), fx),
+                fy,
+            ),
+            fz,
+        )
+    }
+}
+
+// A medium with density from a voxel grid stretched over the box from `min`
+// to `max`. Free flights are sampled with delta tracking and transmittance
+// is estimated with ratio tracking, both against the grid's maximum density.
+pub struct GridMedium {
+    grid: VoxelGrid,
+    min: Point3,
+    max: Point3,
+    max_density: f64,
+    pub extinction: f64,
+    pub albedo: Color,
+    // Scale on the black body emission, which is in cd/m^2: 1 glows as
+    // brightly as a real fire and needs the film's exposure to match. For
+    // scenes lit around 1 cd/m^2, flames near 2000 K need about 1e-6.
+    pub emission_scale: f64,
+    pub phase: HenyeyGreenstein,
+}
+
+impl GridMedium {
+    pub fn new(
+        grid: VoxelGrid,
+        min: Point3,
+        max: Point3,
+        extinction: f64,
+        albedo: Color,
+        phase: HenyeyGreenstein,
+    ) -> Self {
+        let max_density = grid.max_density();
+        GridMedium {
+            grid,
+            min,
+            max,
+            max_density,
+            extinction,
+            albedo,
+            emission_scale: 0.0,
+            phase,
+        }
+    }
+
+    // Makes voxels glow as a black body at their temperature.
+    pub fn with_emission(mut self, emission_scale: f64) -> Self {
+        self.emission_scale = emission_scale;
+        self
+    }
+
+    fn grid_point(&self, p: Point3) -> Vec3 {
+        let size = self.max - self.min;
+        Vec3::new(
+            (p.x - self.min.x) / size.x * self.grid.nx as f64 - 0.5,
+            (p.y - self.min.y) / size.y * self.grid.ny as f64 - 0.5,
+            (p.z - self.min.z) / size.z * self.grid.nz as f64 - 0.5,
+        )
+    }
+
+    fn density(&self, p: Point3) -> f64 {
+        self.grid.lookup(&self.grid.density, self.grid_point(p))
+    }
+
+    fn emission(&self, p: Point3) -> Color {
+        match &self.grid.temperature {
+            Some(temperature) if self.emission_scale > 0.0 => {
+                let kelvin = self.grid.lookup(temperature, self.grid_point(p));
+                if kelvin > 0.0 {
+                    self.emission_scale * blackbody_rgb(kelvin)
+                } else {
+                    Color::new(0.0, 0.0, 0.0)
+                }
+            }
+            _ => Color::new(0.0, 0.0, 0.0),
+        }
+    }
+
+    // Clip the ray to the grid's bounding box.
+    fn clip(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let origin = [ray.origin.x, ray.origin.y, ray.origin.z];
+        let direction: Vec3 = ray.direction.into();
+        let direction = [direction.x, direction.y, direction.z];
+        let min = [self.min.x, self.min.y, self.min.z];
+        let max = [self.max.x, self.max.y, self.max.z];
+
+        let mut t0 = t_min;
+        let mut t1 = t_max;
+        for axis in 0..3 {
+            let inverse = 1.0 / direction[axis];
+            let mut near = (min[axis] - origin[axis]) * inverse;
+            let mut far = (max[axis] - origin[axis]) * inverse;
+            if inverse < 0.0 {
+                std::mem::swap(&mut near, &mut far);
+            }
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+            if t0 >= t1 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
+}
+
+impl Medium for GridMedium {
+    fn sample(&self, rng: &mut SmallRng, ray: &Ray, t_min: f64, t_max: f64) -> MediumSample {
+        let majorant = self.max_density * self.extinction;
+        let (t0, t1) = match self.clip(ray, t_min, t_max) {
+            Some(segment) if majorant > 0.0 => segment,
+            _ => return MediumSample::Pass(Color::new(1.0, 1.0, 1.0)),
+        };
+
+        let mut t = t0;
+        loop {
+            t -= (1.0 - rng.gen::<f64>()).ln() / majorant;
+            if t >= t1 {
+                return MediumSample::Pass(Color::new(1.0, 1.0, 1.0));
+            }
+
+            let point = ray.at(t);
+            if rng.gen::<f64>() * majorant < self.density(point) * self.extinction {
+                let albedo = at_wavelength(self.albedo, ray.wavelength);
+                let emission = at_wavelength(self.emission(point), ray.wavelength);
+                return MediumSample::Scatter {
+                    t,
+                    weight: albedo,
+                    emission: (Color::new(1.0, 1.0, 1.0) - albedo) * emission,
+                    phase: self.phase,
+                };
+            }
+        }
+    }
+
+    fn transmittance(&self, rng: &mut SmallRng, ray: &Ray, t_min: f64, t_max: f64) -> Color {
+        let majorant = self.max_density * self.extinction;
+        let (t0, t1) = match self.clip(ray, t_min, t_max) {
+            Some(segment) if majorant > 0.0 => segment,
+            _ => return Color::new(1.0, 1.0, 1.0),
+        };
+
+        let mut tr = 1.0;
+        let mut t = t0;
+        loop {
+            t -= (1.0 - rng.gen::<f64>()).ln() / majorant;
+            if t >= t1 {
+                return Color::new(tr, tr, tr);
+            }
+            tr *= 1.0 - self.density(ray.at(t)) * self.extinction / majorant;
+        }
+    }
+}
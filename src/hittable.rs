@@ -10,10 +10,18 @@ pub struct Hit {
     pub normal: UnitVec3,
     pub t: f64,
     pub face: Face,
+    pub u: f64,
+    pub v: f64,
 }
 
 impl Hit {
-    pub fn new(root: f64, ray: &Ray, point: Point3, outward_normal: UnitVec3) -> Self {
+    pub fn new(
+        root: f64,
+        ray: &Ray,
+        point: Point3,
+        outward_normal: UnitVec3,
+        (u, v): (f64, f64),
+    ) -> Self {
         let face = if UnitVec3::cos_theta(ray.direction, outward_normal) < 0.0 {
             Face::Front
         } else {
@@ -21,12 +29,21 @@ impl Hit {
         };
         Hit {
             t: root,
-            point: point,
+            point,
             normal: match face {
                 Face::Front => outward_normal,
                 Face::Back => -outward_normal,
             },
-            face: face,
+            face,
+            u,
+            v,
+        }
+    }
+
+    pub fn outward_normal(&self) -> UnitVec3 {
+        match self.face {
+            Face::Front => self.normal,
+            Face::Back => -self.normal,
         }
     }
 }
@@ -34,3 +51,55 @@ impl Hit {
 pub trait Hittable {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit>;
 }
+
+// A point where a ray crosses the surface of a solid.
+#[derive(Copy, Clone)]
+pub struct Crossing {
+    pub t: f64,
+    pub point: Point3,
+    pub outward_normal: UnitVec3,
+    pub u: f64,
+    pub v: f64,
+}
+
+impl Crossing {
+    pub fn from_hit(hit: &Hit) -> Self {
+        Crossing {
+            t: hit.t,
+            point: hit.point,
+            outward_normal: hit.outward_normal(),
+            u: hit.u,
+            v: hit.v,
+        }
+    }
+
+    pub fn flipped(self) -> Self {
+        Crossing {
+            outward_normal: -self.outward_normal,
+            ..self
+        }
+    }
+
+    pub fn to_hit(self, ray: &Ray) -> Hit {
+        Hit::new(
+            self.t,
+            ray,
+            self.point,
+            self.outward_normal,
+            (self.u, self.v),
+        )
+    }
+}
+
+// A stretch of a ray inside a solid.
+#[derive(Copy, Clone)]
+pub struct Interval {
+    pub enter: Crossing,
+    pub exit: Crossing,
+}
+
+// A closed shape that can report every interval along a ray that lies
+// inside it, in order, over the whole line of the ray.
+pub trait Solid: Hittable + Send + Sync {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval>;
+}
@@ -0,0 +1,413 @@
+use super::camera::{Projection, Shutter};
+use super::grid::invalid_data;
+use super::vec3::{CrossProduct, Point3, Ray, UnitVec3, Vec3};
+use rand::{rngs::SmallRng, Rng};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const PUPIL_BINS: usize = 64;
+const PUPIL_SAMPLES: usize = 64;
+
+// One surface of a lens prescription, in millimetres. `radius` is the radius
+// of curvature, positive when the center of curvature is behind the surface,
+// and zero for a flat aperture stop. `thickness` is the distance along the
+// axis to the next surface, `refractive_index` that of the material between
+// them, with zero or one for air, and `aperture` the diameter of the surface.
+#[derive(Copy, Clone)]
+pub struct LensElement {
+    pub radius: f64,
+    pub thickness: f64,
+    pub refractive_index: f64,
+    pub aperture: f64,
+}
+
+// A double Gauss 50 mm f/2 lens, US patent 2,673,491, scaled from 100 mm.
+pub const DOUBLE_GAUSS_50MM: [LensElement; 11] = [
+    LensElement {
+        radius: 29.475,
+        thickness: 3.76,
+        refractive_index: 1.67,
+        aperture: 25.2,
+    },
+    LensElement {
+        radius: 84.83,
+        thickness: 0.12,
+        refractive_index: 1.0,
+        aperture: 25.2,
+    },
+    LensElement {
+        radius: 19.275,
+        thickness: 4.025,
+        refractive_index: 1.67,
+        aperture: 23.0,
+    },
+    LensElement {
+        radius: 40.77,
+        thickness: 3.275,
+        refractive_index: 1.699,
+        aperture: 23.0,
+    },
+    LensElement {
+        radius: 12.75,
+        thickness: 5.705,
+        refractive_index: 1.0,
+        aperture: 18.0,
+    },
+    LensElement {
+        radius: 0.0,
+        thickness: 4.5,
+        refractive_index: 1.0,
+        aperture: 17.1,
+    },
+    LensElement {
+        radius: -14.495,
+        thickness: 1.18,
+        refractive_index: 1.603,
+        aperture: 17.0,
+    },
+    LensElement {
+        radius: 40.77,
+        thickness: 6.065,
+        refractive_index: 1.658,
+        aperture: 20.0,
+    },
+    LensElement {
+        radius: -20.385,
+        thickness: 0.19,
+        refractive_index: 1.0,
+        aperture: 20.0,
+    },
+    LensElement {
+        radius: 437.065,
+        thickness: 3.22,
+        refractive_index: 1.717,
+        aperture: 20.0,
+    },
+    LensElement {
+        radius: -39.73,
+        thickness: 0.0,
+        refractive_index: 1.0,
+        aperture: 20.0,
+    },
+];
+
+impl LensElement {
+    // Loads a prescription with one surface per line, as radius, thickness,
+    // refractive index and aperture, front element first. Text after `#` is
+    // ignored.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Vec<LensElement>> {
+        let text = fs::read_to_string(path)?;
+        let mut elements = vec![];
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap();
+            let values = line
+                .split_whitespace()
+                .map(|token| {
+                    token
+                        .parse::<f64>()
+                        .map_err(|_| invalid_data("lens contains a non-numeric value"))
+                })
+                .collect::<io::Result<Vec<f64>>>()?;
+            match values[..] {
+                [] => {}
+                [radius, thickness, refractive_index, aperture] => elements.push(LensElement {
+                    radius,
+                    thickness,
+                    refractive_index,
+                    aperture,
+                }),
+                _ => return Err(invalid_data("lens surfaces need four values")),
+            }
+        }
+        if elements.is_empty() {
+            return Err(invalid_data("lens has no surfaces"));
+        }
+        Ok(elements)
+    }
+
+    fn index(&self) -> f64 {
+        if self.refractive_index == 0.0 {
+            1.0
+        } else {
+            self.refractive_index
+        }
+    }
+}
+
+// Traces a ray from the film out through the lens. Lens space has the film
+// at z = 0 and the scene towards +z, with surface `i` crossing the axis at
+// `positions[i]`. Returns the ray leaving the front of the lens, or None if
+// the ray is blocked.
+fn trace(
+    elements: &[LensElement],
+    positions: &[f64],
+    mut origin: Vec3,
+    mut direction: UnitVec3,
+) -> Option<(Vec3, UnitVec3)> {
+    for i in (0..elements.len()).rev() {
+        let element = &elements[i];
+        let z = positions[i];
+        let d: Vec3 = direction.into();
+
+        let (point, normal) = if element.radius == 0.0 {
+            let t = (z - origin.z) / d.z;
+            if t <= 0.0 {
+                return None;
+            }
+            (origin + t * d, None)
+        } else {
+            let center = Vec3::new(0.0, 0.0, z - element.radius);
+            let oc = origin - center;
+            let half_b = Vec3::dot(oc, d);
+            let c = oc.length_squared() - element.radius * element.radius;
+            let discriminant = half_b * half_b - c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            // Of the two crossings, the surface is the one nearer its vertex.
+            let sqrtd = discriminant.sqrt();
+            let t = [-half_b - sqrtd, -half_b + sqrtd]
+                .iter()
+                .cloned()
+                .filter(|&t| t > 1e-9)
+                .min_by(|a, b| {
+                    let za = (origin.z + a * d.z - z).abs();
+                    let zb = (origin.z + b * d.z - z).abs();
+                    za.partial_cmp(&zb).unwrap()
+                })?;
+            let point = origin + t * d;
+            (point, Some((point - center).unit_vector()))
+        };
+
+        if point.x * point.x + point.y * point.y > element.aperture * element.aperture / 4.0 {
+            return None;
+        }
+
+        if let Some(normal) = normal {
+            let normal = if direction.cos_theta(normal) > 0.0 {
+                -normal
+            } else {
+                normal
+            };
+            let outside = if i == 0 { 1.0 } else { elements[i - 1].index() };
+            let ratio = element.index() / outside;
+            if ratio * direction.sin_theta(-normal) > 1.0 {
+                return None;
+            }
+            direction = direction.refract(normal, ratio).unit_vector();
+        }
+        origin = point;
+    }
+    Some((origin, direction))
+}
+
+// Where each surface crosses the axis, with the rear surface `film_distance`
+// in front of the film.
+fn positions(elements: &[LensElement], film_distance: f64) -> Vec<f64> {
+    let mut positions = vec![film_distance; elements.len()];
+    for i in (0..elements.len() - 1).rev() {
+        positions[i] = positions[i + 1] + elements[i].thickness;
+    }
+    positions
+}
+
+// The distance in front of the lens that is in focus with the film
+// `film_distance` behind it, found by tracing a ray close to the axis.
+fn focus_distance(elements: &[LensElement], film_distance: f64) -> f64 {
+    let positions = positions(elements, film_distance);
+    let height = 0.001 * elements[elements.len() - 1].aperture;
+    let direction = Vec3::new(height, 0.0, film_distance).unit_vector();
+    let (origin, direction) = match trace(elements, &positions, Vec3::new(0.0, 0.0, 0.0), direction)
+    {
+        Some((origin, direction)) => (origin, Vec3::from(direction)),
+        None => return f64::INFINITY,
+    };
+    // Rays leaving the lens that spread away from the axis, or run parallel
+    // to it, are focused at or beyond infinity.
+    if origin.x * direction.x >= 0.0 {
+        return f64::INFINITY;
+    }
+    let t = -origin.x / direction.x;
+    origin.z + t * direction.z - positions[0]
+}
+
+// Bounds on the rear surface of the lens, (x0, x1, y0, y1), outside which rays
+// from the film can't get through.
+type Bounds = Option<(f64, f64, f64, f64)>;
+
+// A camera tracing rays through a sequence of real lens elements in front of
+// a film `film_diagonal` millimetres across, which gives the aberrations,
+// vignetting and focus breathing of the lens. The scene is measured in metres.
+// Rays that the lens blocks are left black, and the rest are weighted by the
+// area of the pupil they sample and the cos^4 falloff towards the edges of
+// the film, so the center of the film sees the scene's radiance.
+pub struct RealisticCamera {
+    elements: Vec<LensElement>,
+    positions: Vec<f64>,
+    film_width: f64,
+    film_height: f64,
+    // Exit pupil bounds for points on the film at increasing distances from
+    // the center.
+    exit_pupil: Vec<Bounds>,
+    // The area of the exit pupil seen from the center of the film.
+    pupil_area: f64,
+    origin: Point3,
+    u: UnitVec3,
+    v: UnitVec3,
+    w: UnitVec3,
+    shutter: Shutter,
+}
+
+impl RealisticCamera {
+    // The film is moved to bring the point `focus_distance` metres in front of
+    // the lens into focus.
+    pub fn new(
+        elements: Vec<LensElement>,
+        look_from: Point3,
+        look_at: Point3,
+        vup: Vec3,
+        film_diagonal: f64,
+        aspect_ratio: f64,
+        focus_distance: f64,
+    ) -> Self {
+        let film_distance = focus(&elements, 1000.0 * focus_distance);
+        let positions = positions(&elements, film_distance);
+
+        let film_height = film_diagonal / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        let pupils: Vec<(Bounds, f64)> = (0..PUPIL_BINS)
+            .map(|bin| {
+                let r = (bin as f64 + 0.5) / PUPIL_BINS as f64 * film_diagonal / 2.0;
+                exit_pupil(&elements, &positions, r)
+            })
+            .collect();
+        let pupil_area = pupils[0].1;
+        let exit_pupil = pupils.into_iter().map(|(bounds, _)| bounds).collect();
+
+        let w = (look_from - look_at).unit_vector();
+        let u = vup.cross(w).unit_vector();
+        let v = w.cross(u);
+
+        RealisticCamera {
+            elements,
+            positions,
+            film_width: aspect_ratio * film_height,
+            film_height,
+            exit_pupil,
+            pupil_area,
+            origin: look_from,
+            u,
+            v,
+            w,
+            shutter: Shutter::default(),
+        }
+    }
+
+    // Rays are sent at times spread evenly over the shutter interval.
+    pub fn with_shutter(self, open: f64, close: f64) -> Self {
+        RealisticCamera {
+            shutter: Shutter { open, close },
+            ..self
+        }
+    }
+}
+
+// The film distance focusing the lens at `distance` millimetres, by bisection.
+// The focus moves in from infinity as the film moves back from the lens, and
+// a film nearer than the back focal distance focuses beyond infinity, so an
+// infinite `distance` finds the back focal distance.
+fn focus(elements: &[LensElement], distance: f64) -> f64 {
+    let (mut near, mut far) = (1e-3, 1.0);
+    while focus_distance(elements, far) >= distance && far < 1e4 {
+        far *= 2.0;
+    }
+    for _ in 0..100 {
+        let mid = 0.5 * (near + far);
+        if focus_distance(elements, mid) >= distance {
+            near = mid;
+        } else {
+            far = mid;
+        }
+    }
+    0.5 * (near + far)
+}
+
+// Traces a grid of rays from the point `r` along the film's x axis to the rear
+// surface of the lens, and bounds the ones that get through. Also returns the
+// area of the rear surface they pass through.
+fn exit_pupil(elements: &[LensElement], positions: &[f64], r: f64) -> (Bounds, f64) {
+    let rear = elements.len() - 1;
+    let radius = elements[rear].aperture / 2.0;
+    let step = 2.0 * radius / PUPIL_SAMPLES as f64;
+    let film_point = Vec3::new(r, 0.0, 0.0);
+
+    let mut bounds: Bounds = None;
+    let mut area = 0.0;
+    for i in 0..=PUPIL_SAMPLES {
+        for j in 0..=PUPIL_SAMPLES {
+            let (x, y) = (-radius + i as f64 * step, -radius + j as f64 * step);
+            let target = Vec3::new(x, y, positions[rear]);
+            let direction = (target - film_point).unit_vector();
+            if trace(elements, positions, film_point, direction).is_some() {
+                area += step * step;
+                bounds = Some(match bounds {
+                    None => (x, x, y, y),
+                    Some((x0, x1, y0, y1)) => (x0.min(x), x1.max(x), y0.min(y), y1.max(y)),
+                });
+            }
+        }
+    }
+    // Leave room for rays that get through between the samples.
+    let bounds = bounds.map(|(x0, x1, y0, y1)| (x0 - step, x1 + step, y0 - step, y1 + step));
+    (bounds, area)
+}
+
+impl Projection for RealisticCamera {
+    fn get_ray(&self, rng: &mut SmallRng, s: f64, t: f64) -> Option<Ray> {
+        self.get_weighted_ray(rng, s, t).map(|(ray, _)| ray)
+    }
+
+    fn get_weighted_ray(&self, rng: &mut SmallRng, s: f64, t: f64) -> Option<(Ray, f64)> {
+        // The lens forms an upside down image on the film.
+        let x = -(s - 0.5) * self.film_width;
+        let y = -(t - 0.5) * self.film_height;
+        let r = (x * x + y * y).sqrt();
+
+        let diagonal =
+            (self.film_width * self.film_width + self.film_height * self.film_height).sqrt();
+        let bin = ((r / (diagonal / 2.0) * PUPIL_BINS as f64) as usize).min(PUPIL_BINS - 1);
+        let (x0, x1, y0, y1) = self.exit_pupil[bin]?;
+
+        // Sample the pupil bounds for a point on the x axis, then turn them
+        // to the film point's angle.
+        let (px, py) = (
+            x0 + rng.gen::<f64>() * (x1 - x0),
+            y0 + rng.gen::<f64>() * (y1 - y0),
+        );
+        let (sin, cos) = if r > 0.0 { (y / r, x / r) } else { (0.0, 1.0) };
+        let rear = self.elements.len() - 1;
+        let target = Vec3::new(
+            px * cos - py * sin,
+            px * sin + py * cos,
+            self.positions[rear],
+        );
+
+        let film_point = Vec3::new(x, y, 0.0);
+        let towards = (target - film_point).unit_vector();
+        let (origin, direction) = trace(&self.elements, &self.positions, film_point, towards)?;
+
+        // Irradiance on the film goes as cos^4 of the angle to the axis, times
+        // the area sampled, here relative to the pupil at the center.
+        let cos_theta = Vec3::from(towards).z;
+        let weight = cos_theta.powi(4) * (x1 - x0) * (y1 - y0) / self.pupil_area;
+
+        // Lens space to world space, with the front of the lens at the
+        // camera's position.
+        let d: Vec3 = direction.into();
+        let to_world = |p: Vec3| self.u * p.x + self.v * p.y - self.w * p.z;
+        let origin =
+            self.origin + 0.001 * to_world(origin - Vec3::new(0.0, 0.0, self.positions[0]));
+        let ray = Ray::new(origin, to_world(d).unit_vector()).with_time(self.shutter.sample(rng));
+        Some((ray, weight))
+    }
+}
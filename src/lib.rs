@@ -1,9 +1,26 @@
 #[macro_use]
 extern crate derive_more;
 
+pub mod animation;
+pub mod aperture;
 pub mod camera;
+pub mod csg;
+pub mod cylinder;
+pub mod film;
+pub mod grid;
 pub mod hittable;
+pub mod lens;
 pub mod material;
+pub mod matrix;
+pub mod medium;
+pub mod panorama;
+pub mod plane;
+pub mod poly;
+pub mod quad;
 pub mod random;
+pub mod sdf;
+pub mod spectrum;
+pub mod torus;
+pub mod transform;
 pub mod vec3;
 pub mod world;
@@ -1,16 +1,28 @@
 use std::env;
-
-use raytracelib::camera::{Angle, Camera};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::Arc;
+
+use raytracelib::animation::{CameraPath, Interpolation, Keyframes};
+use raytracelib::camera::{Angle, Projection, Shutter, Stereo, StereoPair};
+use raytracelib::csg::{Csg, Operation};
+use raytracelib::film::Film;
+use raytracelib::hittable::Face;
 use raytracelib::material::{Dielectric, Diffuse, Light, Metal, Material, Scatter, ScatterResult};
+use raytracelib::medium::{Homogeneous, Medium, MediumSample};
+use raytracelib::panorama::{Cubemap, Equirectangular, Fisheye, FisheyeMapping, Frame};
+use raytracelib::plane::Plane;
+use raytracelib::quad::{Cuboid, Quad};
 use raytracelib::random::{random_vec3, random_vec3_range};
+use raytracelib::sdf::{Sdf, SdfObject};
+use raytracelib::spectrum::{at_wavelength, sample_wavelength, spectral_sample_to_xyz, xyz_to_rgb};
+use raytracelib::transform::{Keyframed, Pose};
 use raytracelib::vec3::{Color, Point3, Ray, Vec3};
-use raytracelib::world::{Sphere, World};
+use raytracelib::world::{MovingSphere, Object, Sphere, World};
 
 use rand::rngs::SmallRng;
 use rand::{Rng, SeedableRng};
 
-use std::f64::INFINITY;
-
 use rayon::prelude::*;
 
 fn clamp(x: f64, min: f64, max: f64) -> f64 {
@@ -31,8 +43,9 @@ fn to_8bit_color(c: f64) -> i32 {
     (256.0 * clamp(c, 0.0, 0.999)) as i32
 }
 
-fn write_color(color: Color) {
-    println!(
+fn write_color<W: Write>(out: &mut W, color: Color) -> io::Result<()> {
+    writeln!(
+        out,
         "{} {} {}",
         to_8bit_color(color.x.sqrt()),
         to_8bit_color(color.y.sqrt()),
@@ -40,27 +53,115 @@ fn write_color(color: Color) {
     )
 }
 
-fn ray_color(rng: &mut SmallRng, ray: &Ray, world: &World, max_depth: i32) -> Color {
-    if max_depth <= 0 {
+fn write_image<W: Write>(
+    out: &mut W,
+    (width, height): (usize, usize),
+    lines: &[Vec<Color>],
+) -> io::Result<()> {
+    writeln!(out, "P3\n{} {}\n255", width, height)?;
+    for line in lines {
+        for &pixel in line {
+            write_color(out, pixel)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_file(path: &str, size: (usize, usize), lines: &[Vec<Color>]) -> io::Result<()> {
+    write_image(&mut BufWriter::new(File::create(path)?), size, lines)
+}
+
+// Walks through the interior of translucent objects take many short steps, so
+// they are limited separately from other bounces.
+const MAX_INTERIOR_DEPTH: i32 = 256;
+
+// An object a ray has been refracted into, with the medium filling it if it
+// has one, and the object it was inside before that.
+struct Inside<'a> {
+    medium: Option<Homogeneous>,
+    outer: Option<&'a Inside<'a>>,
+}
+
+fn ray_color(
+    rng: &mut SmallRng,
+    ray: &Ray,
+    world: &World,
+    max_depth: i32,
+    interior_depth: i32,
+    inside: Option<&Inside>,
+) -> Color {
+    if max_depth <= 0 || interior_depth <= 0 {
         return Color::new(0.0, 0.0, 0.0);
     }
 
-    if let Some((hit, sphere)) = world.hit(ray, 0.001, INFINITY) {
-        match sphere.material.scatter(rng, ray, &hit) {
+    let surface = world.hit(ray, 0.001, f64::INFINITY);
+    let t_surface = surface.as_ref().map_or(f64::INFINITY, |(hit, _)| hit.t);
+
+    // A ray inside a translucent object travels through its interior rather
+    // than the world's media. Rays that didn't enter it through the surface,
+    // such as those starting inside, find it when they leave through the back.
+    let interior = match inside {
+        Some(inside) => inside.medium,
+        None => surface.as_ref().and_then(|(hit, object)| match hit.face {
+            Face::Back => object.material().interior(),
+            Face::Front => None,
+        }),
+    };
+    let media = match interior {
+        Some(medium) => medium.sample(rng, ray, 0.0, t_surface),
+        None => world.sample_media(rng, ray, t_surface),
+    };
+
+    let weight = match media {
+        MediumSample::Scatter {
+            t,
+            weight,
+            emission,
+            phase,
+        } => {
+            let ray_out = ray.spawn(ray.at(t), phase.sample(rng, ray.direction));
+            let (max_depth, interior_depth) = match interior {
+                Some(_) => (max_depth, interior_depth - 1),
+                None => (max_depth - 1, interior_depth),
+            };
+            return emission
+                + weight * ray_color(rng, &ray_out, world, max_depth, interior_depth, inside);
+        }
+        MediumSample::Pass(weight) => weight,
+    };
+
+    if let Some((hit, object)) = surface {
+        match object.material().scatter(rng, ray, &hit) {
             ScatterResult::Reflect(color, ray_out) => {
-                return color * ray_color(rng, &ray_out, world, max_depth - 1)
+                // Rays refracted through the surface move into or out of the
+                // object.
+                let entered;
+                let inside = match hit.face {
+                    _ if ray_out.direction.cos_theta(hit.normal) >= 0.0 => inside,
+                    Face::Front => {
+                        entered = Inside {
+                            medium: object.material().interior(),
+                            outer: inside,
+                        };
+                        Some(&entered)
+                    }
+                    Face::Back => inside.and_then(|inside| inside.outer),
+                };
+                return weight
+                    * at_wavelength(color, ray.wavelength)
+                    * ray_color(rng, &ray_out, world, max_depth - 1, interior_depth, inside);
             }
             ScatterResult::Absorb => {
                 return Color::new(0.0, 0.0, 0.0);
             }
-            ScatterResult::Emit(color) => return color,
+            ScatterResult::Emit(color) => return weight * at_wavelength(color, ray.wavelength),
         }
     }
     // let light_theta = UnitVec3::cos_theta(ray.direction, Vec3::new(-0.3, -1.0, 0.7).unit_vector());
     // if light_theta <= -0.95 {
     //     Color::new(12.0, 12.0, 12.0)
     // } else {
-    Color::new(0.1, 0.1, 0.15)
+    weight * at_wavelength(Color::new(0.1, 0.1, 0.15), ray.wavelength)
     // }
     // let t = 0.5 * (ray.direction.y + 1.0);
     // return (1.0 - t) * Color::new(1.0, 1.0, 1.0) + t * Color::new(0.5, 0.7, 1.0);
@@ -69,11 +170,14 @@ fn ray_color(rng: &mut SmallRng, ray: &Ray, world: &World, max_depth: i32) -> Co
 fn simple_scene() -> World {
     let glass = Material::Dielectric(Dielectric {
         refractive_index: 1.52,
+        film: None,
+        dispersion: None,
     });
 
     let purple_metal = Material::Metal(Metal {
         albedo: Color::new(0.5, 0.1, 0.5),
         fuzz: 0.0,
+        film: None,
     });
 
     let pink_stone = Material::Diffuse(Diffuse {
@@ -90,63 +194,63 @@ fn simple_scene() -> World {
         //     center: Point3::new(-0.7, 0.0, -1.5),
         //     radius: 0.5,
         // },
-        Sphere {
+        Box::new(Sphere {
             center: Point3::new(0.5, 0.0, -1.0),
             radius: 0.5,
             material: purple_metal,
-        },
-        Sphere {
+        }),
+        Box::new(Sphere {
             center: Point3::new(-0.5, 0.0, -1.0),
             radius: 0.5,
             material: pink_stone.clone(),
-        },
-        Sphere {
+        }),
+        Box::new(Sphere {
             center: Point3::new(1.0, -0.25, -0.5),
             radius: 0.25,
             material: glass.clone(),
-        },
-        Sphere {
+        }),
+        Box::new(Sphere {
             center: Point3::new(0.0, -0.25, -0.5),
             radius: 0.25,
             material: glass,
-        },
-        Sphere {
+        }),
+        Box::new(Sphere {
             center: Point3::new(0.0, -0.25, -0.5),
             radius: 0.2,
             material: pink_stone,
-        },
-        Sphere {
+        }),
+        Box::new(Sphere {
             center: Point3::new(0.0, 0.0, 2.0),
             radius: 0.5,
             material: light_source,
-        },
+        }),
         // Sphere {
         //     center: Point3::new(0.7, 0.0, -1.5),
         //     radius: 0.5,
         // },
 
         // Ground
-        Sphere {
-            center: Point3::new(0.0, -10000.5, -1.0),
-            radius: 10000.0,
-            material: Material::Diffuse(Diffuse {
+        Box::new(Plane::new(
+            Point3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &Material::Diffuse(Diffuse {
                 albedo: Color::new(0.8, 0.8, 0.8),
             }),
-        },
+        )),
     ])
 }
 
 fn _random_scene<R: Rng>(rng: &mut R) -> World {
-    let mut world = vec![];
+    let mut world: Vec<Box<dyn Object>> = vec![];
 
     let ground_material = Material::Diffuse(Diffuse {
         albedo: Color::new(0.5, 0.5, 0.5),
     });
-    world.push(Sphere {
-        center: Point3::new(0.0, -1000.0, 0.0),
-        radius: 1000.0,
-        material: ground_material,
-    });
+    world.push(Box::new(Plane::new(
+        Point3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+        &ground_material,
+    )));
 
     for a in -5..5 {
         for b in -5..5 {
@@ -160,109 +264,614 @@ fn _random_scene<R: Rng>(rng: &mut R) -> World {
                 let material = match rng.gen::<f64>() {
                     x if x < 0.8 => {
                         let albedo = random_vec3(rng) * random_vec3(rng);
-                        Material::Diffuse(Diffuse { albedo: albedo })
+                        Material::Diffuse(Diffuse { albedo })
                     }
                     x if x < 0.95 => {
                         let albedo = random_vec3_range(rng, 0.5, 1.0);
                         let fuzz: f64 = rng.gen_range(0.0, 0.5);
-                        Material::Metal(Metal { albedo, fuzz })
+                        Material::Metal(Metal {
+                            albedo,
+                            fuzz,
+                            film: None,
+                        })
                     }
                     _ => Material::Dielectric(Dielectric {
                         refractive_index: 1.52,
+                        film: None,
+                        dispersion: None,
                     }),
                 };
-                world.push(Sphere {
+                world.push(Box::new(Sphere {
                     center,
                     radius: 0.2,
                     material,
-                })
+                }))
             }
         }
     }
 
-    world.push(Sphere {
+    world.push(Box::new(Sphere {
         center: Point3::new(0.0, 1.0, 0.0),
         radius: 1.0,
         material: Material::Dielectric(Dielectric {
             refractive_index: 1.52,
+            film: None,
+            dispersion: None,
         }),
-    });
-    world.push(Sphere {
+    }));
+    world.push(Box::new(Sphere {
         center: Point3::new(-4.0, 1.0, 0.0),
         radius: 1.0,
         material: Material::Diffuse(Diffuse {
             albedo: Color::new(0.4, 0.2, 0.1),
         }),
-    });
-    world.push(Sphere {
+    }));
+    world.push(Box::new(Sphere {
         center: Point3::new(4.0, 1.0, 0.0),
         radius: 1.0,
         material: Material::Metal(Metal {
             albedo: Color::new(0.7, 0.6, 0.5),
             fuzz: 0.0,
+            film: None,
         }),
-    });
+    }));
 
     World::new(world)
 }
 
+// The standard Cornell box, to be viewed from (278, 278, -800) looking at
+// (278, 278, 0) with a 40 degree vertical field of view.
+fn _cornell_box_scene() -> World {
+    let red = Material::Diffuse(Diffuse {
+        albedo: Color::new(0.65, 0.05, 0.05),
+    });
+    let white = Material::Diffuse(Diffuse {
+        albedo: Color::new(0.73, 0.73, 0.73),
+    });
+    let green = Material::Diffuse(Diffuse {
+        albedo: Color::new(0.12, 0.45, 0.15),
+    });
+    let light = Material::Light(Light {
+        color: Color::new(15.0, 15.0, 15.0),
+    });
+
+    let block = |corner: Point3, height: f64, angle: Angle| {
+        let (sin, cos) = (angle.sin(), angle.cos());
+        Cuboid::from_edges(
+            corner,
+            [
+                Vec3::new(165.0 * cos, 0.0, -165.0 * sin),
+                Vec3::new(0.0, height, 0.0),
+                Vec3::new(165.0 * sin, 0.0, 165.0 * cos),
+            ],
+            &white,
+        )
+    };
+
+    World::new(vec![
+        Box::new(Quad::yz(0.0, 555.0, 0.0, 555.0, 555.0, &green)),
+        Box::new(Quad::yz(0.0, 555.0, 0.0, 555.0, 0.0, &red)),
+        Box::new(Quad::xz(213.0, 343.0, 227.0, 332.0, 554.0, &light)),
+        Box::new(Quad::xz(0.0, 555.0, 0.0, 555.0, 0.0, &white)),
+        Box::new(Quad::xz(0.0, 555.0, 0.0, 555.0, 555.0, &white)),
+        Box::new(Quad::xy(0.0, 555.0, 0.0, 555.0, 555.0, &white)),
+        Box::new(block(
+            Point3::new(265.0, 0.0, 295.0),
+            330.0,
+            Angle::from_degrees(15.0),
+        )),
+        Box::new(block(
+            Point3::new(130.0, 0.0, 65.0),
+            165.0,
+            Angle::from_degrees(-18.0),
+        )),
+    ])
+}
+
 // Image:
 const ASPECT_RATIO: f64 = 3.0 / 2.0;
 const IMAGE_WIDTH: i64 = 400;
-const IMAGE_HEIGHT: i64 = (IMAGE_WIDTH as f64 / ASPECT_RATIO) as i64;
 
-fn main() {
+fn _csg_scene() -> World {
+    let glass = Material::Dielectric(Dielectric {
+        refractive_index: 1.52,
+        film: None,
+        dispersion: None,
+    });
+    let gold = Material::Metal(Metal {
+        albedo: Color::new(0.8, 0.6, 0.2),
+        fuzz: 0.1,
+        film: None,
+    });
+    let stone = Material::Diffuse(Diffuse {
+        albedo: Color::new(0.3, 0.5, 0.7),
+    });
+
+    // A biconvex lens: the overlap of two large spheres.
+    let lens = Csg::new(
+        Operation::Intersection,
+        Sphere::new(Point3::new(-1.0, 0.2, -0.8), 1.2, &glass),
+        Sphere::new(Point3::new(-1.0, 0.2, 1.2), 1.2, &glass),
+        &glass,
+    );
+
+    // A metal sphere with a hole cut through it by a box.
+    let cut_sphere = Csg::new(
+        Operation::Difference,
+        Sphere::new(Point3::new(0.5, 0.0, -1.0), 0.5, &gold),
+        Cuboid::new(
+            Point3::new(0.3, -0.2, -1.6),
+            Point3::new(0.7, 0.2, -0.4),
+            &gold,
+        ),
+        &gold,
+    );
+
+    // A rounded box: a cube with its corners trimmed by a sphere.
+    let rounded_box = Csg::new(
+        Operation::Intersection,
+        Cuboid::new(
+            Point3::new(0.8, -0.5, -0.2),
+            Point3::new(1.4, 0.1, 0.4),
+            &stone,
+        ),
+        Sphere::new(Point3::new(1.1, -0.2, 0.1), 0.4, &stone),
+        &stone,
+    );
+
+    World::new(vec![
+        Box::new(lens),
+        Box::new(cut_sphere),
+        Box::new(rounded_box),
+        Box::new(Plane::new(
+            Point3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &Material::Diffuse(Diffuse {
+                albedo: Color::new(0.8, 0.8, 0.8),
+            }),
+        )),
+    ])
+}
+
+fn _sdf_scene() -> World {
+    let red = Material::Diffuse(Diffuse {
+        albedo: Color::new(0.7, 0.2, 0.2),
+    });
+    let steel = Material::Metal(Metal {
+        albedo: Color::new(0.7, 0.7, 0.75),
+        fuzz: 0.05,
+        film: None,
+    });
+    let glass = Material::Dielectric(Dielectric {
+        refractive_index: 1.5,
+        film: None,
+        dispersion: None,
+    });
+
+    // A blob of a sphere melted into a rounded box.
+    let blob = Sdf::SmoothUnion {
+        a: Box::new(Sdf::RoundedCuboid {
+            half_size: Vec3::new(0.3, 0.2, 0.3),
+            radius: 0.05,
+        }),
+        b: Box::new(Sdf::Translate {
+            offset: Vec3::new(0.0, 0.25, 0.0),
+            inner: Box::new(Sdf::Sphere { radius: 0.2 }),
+        }),
+        smoothness: 0.1,
+    };
+
+    // A twisted bar with a torus shaped groove cut around it.
+    let bar = Sdf::Subtraction {
+        a: Box::new(Sdf::Twist {
+            rate: 3.0,
+            inner: Box::new(Sdf::Cuboid {
+                half_size: Vec3::new(0.12, 0.45, 0.12),
+            }),
+        }),
+        b: Box::new(Sdf::Torus {
+            major_radius: 0.17,
+            minor_radius: 0.05,
+        }),
+    };
+
+    // A row of small spheres, repeated along x.
+    let row = Sdf::Repeat {
+        spacing: Vec3::new(0.25, 0.0, 0.0),
+        inner: Box::new(Sdf::Sphere { radius: 0.08 }),
+    };
+
+    World::new(vec![
+        Box::new(SdfObject::new(
+            blob,
+            Point3::new(-0.6, -0.3, -1.0),
+            0.6,
+            &red,
+        )),
+        Box::new(
+            SdfObject::new(bar, Point3::new(0.2, -0.05, -1.0), 0.6, &steel).with_step_scale(0.5),
+        ),
+        Box::new(SdfObject::new(
+            row,
+            Point3::new(0.0, -0.42, -0.3),
+            1.0,
+            &glass,
+        )),
+        Box::new(Plane::new(
+            Point3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &Material::Diffuse(Diffuse {
+                albedo: Color::new(0.8, 0.8, 0.8),
+            }),
+        )),
+    ])
+}
+
+fn _motion_blur_scene() -> World {
+    let blue = Material::Diffuse(Diffuse {
+        albedo: Color::new(0.2, 0.3, 0.7),
+    });
+    let orange = Material::Diffuse(Diffuse {
+        albedo: Color::new(0.8, 0.4, 0.1),
+    });
+    let light = Material::Light(Light {
+        color: Color::new(4.0, 4.0, 4.0),
+    });
+
+    // A cube spinning half a turn while rising, over the shutter interval.
+    let cube = Arc::new(Cuboid::new(
+        Point3::new(-0.2, -0.2, -0.2),
+        Point3::new(0.2, 0.2, 0.2),
+        &orange,
+    ));
+    let spinning = Keyframed::new(
+        cube,
+        Keyframes::new(
+            vec![
+                (0.0, Pose::new(Vec3::new(0.5, -0.3, -1.0))),
+                (
+                    1.0,
+                    Pose {
+                        rotation: Vec3::new(0.0, 90.0, 0.0),
+                        ..Pose::new(Vec3::new(0.5, -0.1, -1.0))
+                    },
+                ),
+            ],
+            Interpolation::Linear,
+        ),
+    );
+
+    World::new(vec![
+        Box::new(MovingSphere::new(
+            (Point3::new(-0.7, -0.25, -1.0), 0.0),
+            (Point3::new(-0.3, -0.25, -1.0), 1.0),
+            0.25,
+            &blue,
+        )),
+        Box::new(spinning),
+        Box::new(Sphere::new(Point3::new(0.0, 3.0, 1.0), 1.5, &light)),
+        Box::new(Plane::new(
+            Point3::new(0.0, -0.5, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            &Material::Diffuse(Diffuse {
+                albedo: Color::new(0.8, 0.8, 0.8),
+            }),
+        )),
+    ])
+}
+
+// A camera circling `center` once every `seconds`, at `height` above it.
+fn turntable(
+    center: Point3,
+    radius: f64,
+    height: f64,
+    seconds: f64,
+    aspect_ratio: f64,
+) -> CameraPath {
+    let keys = (0..=8)
+        .map(|i| {
+            let angle = Angle::from_degrees(45.0 * i as f64);
+            let offset = Vec3::new(radius * angle.sin(), height, radius * angle.cos());
+            (seconds * i as f64 / 8.0, center + offset)
+        })
+        .collect();
+    CameraPath {
+        look_from: Keyframes::new(keys, Interpolation::CatmullRom),
+        look_at: Keyframes::constant(center),
+        vfov: Keyframes::constant(Angle::from_degrees(30.0)),
+        vup: Vec3::new(0.0, 1.0, 0.0),
+        aspect_ratio,
+        aperture: 0.0,
+    }
+}
+
+// The views for an image: one, or the left and right eyes when `stereo` gives
+// an interocular distance and convergence.
+fn views<P: Stereo + 'static>(center: P, stereo: Option<(f64, f64)>) -> Vec<Box<dyn Projection>> {
+    match stereo {
+        None => vec![Box::new(center)],
+        Some((interocular, convergence)) => {
+            let pair = StereoPair::new(&center, interocular, convergence);
+            vec![Box::new(pair.left), Box::new(pair.right)]
+        }
+    }
+}
+
+// The camera at `time`, with the shutter open for `shutter` seconds, by the
+// name given with `--projection`. The panoramic projections are placed where
+// the perspective camera would be.
+fn projection(
+    name: Option<&str>,
+    path: &CameraPath,
+    (time, shutter): (f64, f64),
+    stereo: Option<(f64, f64)>,
+) -> Vec<Box<dyn Projection>> {
+    let frame = Frame::new(path.look_from.at(time), path.look_at.at(time), path.vup);
+    let shutter_interval = Shutter {
+        open: time,
+        close: time + shutter,
+    };
+    if stereo.is_some() && (name == Some("cubemap") || name == Some("fisheye")) {
+        panic!("no stereo mode for {} projection", name.unwrap());
+    }
+    match name {
+        None | Some("perspective") => views(path.camera(time, shutter), stereo),
+        Some("equirectangular") => views(
+            Equirectangular {
+                shutter: shutter_interval,
+                ..Equirectangular::new(frame)
+            },
+            stereo,
+        ),
+        Some("cubemap") => vec![Box::new(Cubemap {
+            shutter: shutter_interval,
+            ..Cubemap::new(frame)
+        })],
+        Some("fisheye") => vec![Box::new(Fisheye {
+            shutter: shutter_interval,
+            ..Fisheye::new(
+                frame,
+                Angle::from_degrees(180.0),
+                FisheyeMapping::Equisolid,
+                path.aspect_ratio,
+            )
+        })],
+        Some(name) => panic!("unknown projection {}", name),
+    }
+}
+
+// Writes rendered views to `name`.ppm, or to stdout without a name. Stereo
+// pairs are put side by side or over and under in one image, or written to
+// separate `_left` and `_right` files.
+fn output(
+    images: Vec<Vec<Vec<Color>>>,
+    film: &Film,
+    layout: &str,
+    name: Option<&str>,
+) -> io::Result<()> {
+    let (width, height) = (film.columns().count(), film.rows().count());
+    let write = |lines: &[Vec<Color>], size: (usize, usize)| match name {
+        Some(name) => write_file(&format!("{}.ppm", name), size, lines),
+        None => write_image(&mut io::stdout().lock(), size, lines),
+    };
+
+    let mut images = images.into_iter();
+    let left = images.next().unwrap();
+    let right = match images.next() {
+        Some(right) => right,
+        None => return write(&left, (width, height)),
+    };
+    match layout {
+        "side-by-side" => {
+            let lines: Vec<Vec<Color>> = left
+                .into_iter()
+                .zip(right)
+                .map(|(l, r)| l.into_iter().chain(r).collect())
+                .collect();
+            write(&lines, (2 * width, height))
+        }
+        "over-under" => {
+            let lines: Vec<Vec<Color>> = left.into_iter().chain(right).collect();
+            write(&lines, (width, 2 * height))
+        }
+        "separate" => {
+            let name = name.unwrap_or("stereo");
+            write_file(&format!("{}_left.ppm", name), (width, height), &left)?;
+            write_file(&format!("{}_right.ppm", name), (width, height), &right)
+        }
+        _ => panic!("unknown stereo layout {}", layout),
+    }
+}
+
+// The values following `--name` on the command line.
+fn flag_values<'a>(args: &'a [String], name: &str, count: usize) -> Option<&'a [String]> {
+    let i = args.iter().position(|arg| arg == name)?;
+    if args.len() < i + 1 + count {
+        panic!("{} needs {} values", name, count);
+    }
+    Some(&args[i + 1..i + 1 + count])
+}
+
+fn main() -> io::Result<()> {
     let args: Vec<String> = env::args().collect();
     let samples_per_pixel: i64 = args[1].parse::<i64>().unwrap();
+    let spectral = args.iter().any(|arg| arg == "--spectral");
+    // `--frames START END` renders the frames in that range to numbered files
+    // instead of a single image to stdout.
+    let frames = flag_values(&args, "--frames", 2).map(|values| {
+        let start: i64 = values[0].parse().unwrap();
+        let end: i64 = values[1].parse().unwrap();
+        start..=end
+    });
+    let fps: f64 = flag_values(&args, "--fps", 1).map_or(24.0, |values| values[0].parse().unwrap());
+    // `--stereo LAYOUT` renders a view for each eye, laid out `side-by-side`,
+    // `over-under` or in `separate` files. The eyes are `--interocular`
+    // apart, and converge on the point the camera looks at unless
+    // `--convergence` is given.
+    let layout = flag_values(&args, "--stereo", 1).map(|values| values[0].as_str());
+    let interocular: f64 =
+        flag_values(&args, "--interocular", 1).map_or(0.064, |values| values[0].parse().unwrap());
+    let convergence: Option<f64> =
+        flag_values(&args, "--convergence", 1).map(|values| values[0].parse().unwrap());
+    // `--resolution WIDTH HEIGHT` sets the size of the frame and
+    // `--pixel-aspect` the width over height of its pixels. `--crop X0 X1 Y0
+    // Y1` renders only that part of the frame, given as fractions of its width
+    // and height from the top left.
+    let (width, height) = flag_values(&args, "--resolution", 2).map_or(
+        (
+            IMAGE_WIDTH,
+            (IMAGE_WIDTH as f64 / ASPECT_RATIO).round() as i64,
+        ),
+        |values| (values[0].parse().unwrap(), values[1].parse().unwrap()),
+    );
+    let mut film = Film::new(width, height);
+    if let Some(values) = flag_values(&args, "--pixel-aspect", 1) {
+        film = film.with_pixel_aspect(values[0].parse().unwrap());
+    }
+    if let Some(values) = flag_values(&args, "--crop", 4) {
+        let crop: Vec<f64> = values.iter().map(|value| value.parse().unwrap()).collect();
+        film = film
+            .with_crop((crop[0], crop[1]), (crop[2], crop[3]))
+            .unwrap_or_else(|| panic!("crop window {:?} covers none of the frame", crop));
+    }
+    // Exposure is set by `--ev100 EV` or by `--exposure F_NUMBER SHUTTER ISO`,
+    // and `--white-balance KELVIN` neutralises light of that colour
+    // temperature.
+    if let Some(values) = flag_values(&args, "--ev100", 1) {
+        film = film.with_ev100(values[0].parse().unwrap());
+    }
+    if let Some(values) = flag_values(&args, "--exposure", 3) {
+        let settings: Vec<f64> = values.iter().map(|value| value.parse().unwrap()).collect();
+        film = film.with_camera_settings(settings[0], settings[1], settings[2]);
+    }
+    if let Some(values) = flag_values(&args, "--white-balance", 1) {
+        let temperature: f64 = values[0].parse().unwrap();
+        film = film
+            .with_white_balance(temperature)
+            .unwrap_or_else(|| panic!("no light to white balance at {} K", temperature));
+    }
 
     let world = simple_scene();
-    //
+    // Only paths carrying a wavelength can follow light split by dispersion.
+    let spectral = spectral || world.is_dispersive();
     // Camera:
     let look_from = Point3::new(2.5, 2.5, 2.5);
     let look_at = Point3::new(1.0, -0.25, -0.5);
-    let dist_to_focus = (look_from - look_at).length();
-    let aperture = 0.2;
-
-    let camera = Camera::new(
-        look_from,
-        look_at,
-        Vec3::new(0.0, 1.0, 0.0),
-        Angle::from_degrees(15.0),
-        16.0 / 9.0,
-        aperture,
-        dist_to_focus,
-    );
-
-    // Render
-    println!("P3\n{} {}\n255", IMAGE_WIDTH, IMAGE_HEIGHT);
-    
-    let lines : Vec<i64> = (0..IMAGE_HEIGHT).rev().collect();
-    let lines : Vec<Vec<Color>> = lines.par_iter().map(|j| {
-        eprint!("\rRender scanline: {} ", j);
-
-        render_line(*j, samples_per_pixel, &world, &camera)
-    }).collect();
+    // `--turntable SECONDS` circles the camera around the point it looks at
+    // once every SECONDS, keeping its distance and height.
+    let path = match flag_values(&args, "--turntable", 1) {
+        Some(values) => {
+            let offset = look_from - look_at;
+            turntable(
+                look_at,
+                (offset.x * offset.x + offset.z * offset.z).sqrt(),
+                offset.y,
+                values[0].parse().unwrap(),
+                film.aspect_ratio(),
+            )
+        }
+        None => CameraPath {
+            look_from: Keyframes::constant(look_from),
+            look_at: Keyframes::constant(look_at),
+            vfov: Keyframes::constant(Angle::from_degrees(15.0)),
+            vup: Vec3::new(0.0, 1.0, 0.0),
+            aspect_ratio: film.aspect_ratio(),
+            aperture: 0.2,
+        },
+    };
+    let projection_name = flag_values(&args, "--projection", 1).map(|values| values[0].as_str());
 
-    for line in lines{
-        for pixel in line {
-            write_color(pixel);
+    let stereo = |time: f64| {
+        layout.map(|_| {
+            let distance = (path.look_from.at(time) - path.look_at.at(time)).length();
+            (interocular, convergence.unwrap_or(distance))
+        })
+    };
+    let render_all = |cameras: Vec<Box<dyn Projection>>| {
+        cameras
+            .iter()
+            .map(|camera| {
+                let lines = render(samples_per_pixel, spectral, &world, camera.as_ref(), &film);
+                lines
+                    .into_iter()
+                    .map(|line| line.into_iter().map(|pixel| film.develop(pixel)).collect())
+                    .collect()
+            })
+            .collect()
+    };
+    let layout = layout.unwrap_or("");
+
+    match frames {
+        Some(frames) => {
+            for frame in frames {
+                // The shutter stays open for half of each frame.
+                let time = frame as f64 / fps;
+                let cameras = projection(projection_name, &path, (time, 0.5 / fps), stereo(time));
+                let name = format!("frame_{:04}", frame);
+                output(render_all(cameras), &film, layout, Some(&name))?;
+                eprintln!("\rWrote frame {}        ", frame);
+            }
+        }
+        None => {
+            let cameras = projection(projection_name, &path, (0.0, 1.0), stereo(0.0));
+            output(render_all(cameras), &film, layout, None)?;
         }
     }
+    Ok(())
+}
+
+fn render(
+    samples_per_pixel: i64,
+    spectral: bool,
+    world: &World,
+    camera: &dyn Projection,
+    film: &Film,
+) -> Vec<Vec<Color>> {
+    let lines: Vec<i64> = film.rows().map(|row| film.height - 1 - row).collect();
+    lines
+        .par_iter()
+        .map(|j| {
+            eprint!("\rRender scanline: {} ", j);
+
+            render_line(*j, samples_per_pixel, spectral, world, camera, film)
+        })
+        .collect()
 }
 
-fn render_line(j: i64, samples_per_pixel: i64, world: &World, camera: &Camera) -> Vec<Color> {
+fn render_line(
+    j: i64,
+    samples_per_pixel: i64,
+    spectral: bool,
+    world: &World,
+    camera: &dyn Projection,
+    film: &Film,
+) -> Vec<Color> {
     let mut rng = SmallRng::from_entropy();
     let max_depth = 20;
 
     let mut result = vec![];
-    for i in 0..IMAGE_WIDTH {
+    for i in film.columns() {
         let mut pixel_color = Color::new(0.0, 0.0, 0.0);
 
         for _ in 0..samples_per_pixel {
-            let u = (i as f64 + rng.gen::<f64>()) / (IMAGE_WIDTH - 1) as f64;
-            let v = (j as f64 + rng.gen::<f64>()) / (IMAGE_HEIGHT - 1) as f64;
-            let ray = camera.get_ray(&mut rng, u, v);
-            pixel_color = pixel_color + ray_color(&mut rng, &ray, world, max_depth);
+            let u = (i as f64 + rng.gen::<f64>()) / film.width as f64;
+            let v = (j as f64 + rng.gen::<f64>()) / film.height as f64;
+            let (ray, weight) = match camera.get_weighted_ray(&mut rng, u, v) {
+                Some(ray) => ray,
+                None => continue,
+            };
+            if spectral {
+                let wavelength = sample_wavelength(&mut rng);
+                let ray = ray.with_wavelength(wavelength);
+                let radiance = weight
+                    * ray_color(&mut rng, &ray, world, max_depth, MAX_INTERIOR_DEPTH, None).y;
+                pixel_color =
+                    pixel_color + xyz_to_rgb(spectral_sample_to_xyz(wavelength, radiance));
+            } else {
+                pixel_color = pixel_color
+                    + weight
+                        * ray_color(&mut rng, &ray, world, max_depth, MAX_INTERIOR_DEPTH, None);
+            }
         }
 
         result.push(pixel_color / samples_per_pixel as f64)
@@ -1,7 +1,10 @@
 use super::hittable::{Face, Hit};
-use super::random::{random_in_unit_sphere, random_unit_vector};
-use super::vec3::{Color, Ray, Vec3};
+use super::medium::{transmittance, HenyeyGreenstein, Homogeneous};
+use super::random::{random_cosine_direction, random_in_unit_sphere, random_unit_vector};
+use super::spectrum::rgb_to_spectrum;
+use super::vec3::{Color, Onb, Ray, Vec3};
 use rand::{rngs::SmallRng, Rng};
+use std::f64::consts::PI;
 
 pub enum ScatterResult {
     Reflect(Color, Ray),
@@ -19,14 +22,69 @@ pub struct Diffuse {
 }
 
 impl Scatter for Diffuse {
-    fn scatter(&self, rng: &mut SmallRng, _ray_in: &Ray, hit: &Hit) -> ScatterResult {
+    fn scatter(&self, rng: &mut SmallRng, ray_in: &Ray, hit: &Hit) -> ScatterResult {
         let mut scatter_direction: Vec3 = hit.normal + random_unit_vector(rng);
 
         if scatter_direction.near_zero() {
             scatter_direction = hit.normal.into()
         }
 
-        ScatterResult::Reflect(self.albedo, Ray::new(hit.point, scatter_direction.into()))
+        ScatterResult::Reflect(
+            self.albedo,
+            ray_in.spawn(hit.point, scatter_direction.into()),
+        )
+    }
+}
+
+// Oren-Nayar rough diffuse reflection. `sigma` is the standard deviation of
+// the microfacet slope angle in radians; zero gives Lambertian reflection.
+#[derive(Copy, Clone)]
+pub struct OrenNayar {
+    pub albedo: Color,
+    pub sigma: f64,
+}
+
+impl OrenNayar {
+    fn coefficients(&self) -> (f64, f64) {
+        let sigma2 = self.sigma * self.sigma;
+        (
+            1.0 - 0.5 * sigma2 / (sigma2 + 0.33),
+            0.45 * sigma2 / (sigma2 + 0.09),
+        )
+    }
+}
+
+impl Scatter for OrenNayar {
+    fn scatter(&self, rng: &mut SmallRng, ray_in: &Ray, hit: &Hit) -> ScatterResult {
+        let onb = Onb::from_w(hit.normal);
+        let direction = onb.local(random_cosine_direction(rng)).unit_vector();
+        let view = -ray_in.direction;
+
+        let cos_in = direction.cos_theta(hit.normal);
+        let cos_out = f64::max(view.cos_theta(hit.normal), 1e-8);
+        let sin_in = direction.sin_theta(hit.normal);
+        let sin_out = view.sin_theta(hit.normal);
+
+        let tangent_in = direction - cos_in * hit.normal;
+        let tangent_out = view - cos_out * hit.normal;
+        let cos_phi = if tangent_in.near_zero() || tangent_out.near_zero() {
+            0.0
+        } else {
+            Vec3::dot(tangent_in, tangent_out) / (tangent_in.length() * tangent_out.length())
+        };
+
+        let (sin_alpha, tan_beta) = if cos_in < cos_out {
+            (sin_in, sin_out / cos_out)
+        } else {
+            (sin_out, sin_in / f64::max(cos_in, 1e-8))
+        };
+
+        // With cosine weighted sampling the cos/pdf terms cancel, leaving only
+        // the Oren-Nayar factor.
+        let (a, b) = self.coefficients();
+        let weight = a + b * f64::max(cos_phi, 0.0) * sin_alpha * tan_beta;
+
+        ScatterResult::Reflect(self.albedo * weight, ray_in.spawn(hit.point, direction))
     }
 }
 
@@ -34,14 +92,22 @@ impl Scatter for Diffuse {
 pub struct Metal {
     pub albedo: Color,
     pub fuzz: f64,
+    pub film: Option<ThinFilm>,
 }
 
 impl Scatter for Metal {
     fn scatter(&self, rng: &mut SmallRng, ray_in: &Ray, hit: &Hit) -> ScatterResult {
         let reflected = ray_in.direction.reflect(hit.normal);
+        let attenuation = match self.film {
+            Some(film) => {
+                let cos_theta = f64::max(ray_in.direction.cos_theta(-hit.normal), 0.0);
+                film.metal_reflectance(cos_theta, self.albedo, ray_in.wavelength)
+            }
+            None => self.albedo,
+        };
         ScatterResult::Reflect(
-            self.albedo,
-            Ray::new(
+            attenuation,
+            ray_in.spawn(
                 hit.point,
                 (reflected + self.fuzz * random_in_unit_sphere(rng)).unit_vector(),
             ),
@@ -49,26 +115,151 @@ impl Scatter for Metal {
     }
 }
 
+// A thin transparent film on top of a surface, as in soap bubbles or oil on
+// metal. `thickness` is in nanometres. Reflectance comes from interference
+// between the two film boundaries, evaluated at a wavelength for each of the
+// red, green and blue channels.
+#[derive(Copy, Clone)]
+pub struct ThinFilm {
+    pub thickness: f64,
+    pub refractive_index: f64,
+}
+
+const RGB_WAVELENGTHS: [f64; 3] = [650.0, 532.0, 450.0];
+
+fn fresnel_amplitudes(cos_i: f64, cos_t: f64, n_i: f64, n_t: f64) -> (f64, f64) {
+    let s = (n_i * cos_i - n_t * cos_t) / (n_i * cos_i + n_t * cos_t);
+    let p = (n_t * cos_i - n_i * cos_t) / (n_t * cos_i + n_i * cos_t);
+    (s, p)
+}
+
+fn airy_reflectance(r12: f64, r23: f64, phase: f64) -> f64 {
+    let cross = 2.0 * r12 * r23 * phase.cos();
+    (r12 * r12 + r23 * r23 + cross) / (1.0 + r12 * r12 * r23 * r23 + cross)
+}
+
+fn refracted_cos(cos_theta: f64, n_i: f64, n_t: f64) -> Option<f64> {
+    let sin_t = n_i * (1.0 - cos_theta * cos_theta).max(0.0).sqrt() / n_t;
+    if sin_t >= 1.0 {
+        None
+    } else {
+        Some((1.0 - sin_t * sin_t).sqrt())
+    }
+}
+
+impl ThinFilm {
+    fn phase(&self, cos_film: f64, wavelength: f64) -> f64 {
+        4.0 * PI * self.refractive_index * self.thickness * cos_film / wavelength
+    }
+
+    pub fn dielectric_reflectance(
+        &self,
+        cos_theta: f64,
+        outer_index: f64,
+        inner_index: f64,
+        wavelength: f64,
+    ) -> f64 {
+        let film_index = self.refractive_index;
+        let (cos_film, cos_inner) = match (
+            refracted_cos(cos_theta, outer_index, film_index),
+            refracted_cos(cos_theta, outer_index, inner_index),
+        ) {
+            (Some(cos_film), Some(cos_inner)) => (cos_film, cos_inner),
+            _ => return 1.0,
+        };
+
+        let (s12, p12) = fresnel_amplitudes(cos_theta, cos_film, outer_index, film_index);
+        let (s23, p23) = fresnel_amplitudes(cos_film, cos_inner, film_index, inner_index);
+        let phase = self.phase(cos_film, wavelength);
+        0.5 * (airy_reflectance(s12, s23, phase) + airy_reflectance(p12, p23, phase))
+    }
+
+    // The metal is treated as a perfect phase inverting mirror with amplitude
+    // reflectance taken from its albedo.
+    pub fn metal_reflectance(
+        &self,
+        cos_theta: f64,
+        albedo: Color,
+        wavelength: Option<f64>,
+    ) -> Color {
+        let cos_film = match refracted_cos(cos_theta, 1.0, self.refractive_index) {
+            Some(cos_film) => cos_film,
+            None => return albedo,
+        };
+        let (s12, p12) = fresnel_amplitudes(cos_theta, cos_film, 1.0, self.refractive_index);
+
+        let channel = |wavelength: f64, base: f64| {
+            let r23 = -base.max(0.0).sqrt();
+            let phase = self.phase(cos_film, wavelength);
+            0.5 * (airy_reflectance(s12, r23, phase) + airy_reflectance(p12, r23, phase))
+        };
+        match wavelength {
+            Some(wavelength) => {
+                let reflectance = channel(wavelength, rgb_to_spectrum(albedo, wavelength));
+                Color::new(reflectance, reflectance, reflectance)
+            }
+            None => Color::new(
+                channel(RGB_WAVELENGTHS[0], albedo.x),
+                channel(RGB_WAVELENGTHS[1], albedo.y),
+                channel(RGB_WAVELENGTHS[2], albedo.z),
+            ),
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Dielectric {
     pub refractive_index: f64,
+    pub film: Option<ThinFilm>,
+    pub dispersion: Option<Dispersion>,
+}
+
+// Wavelength dependent refractive index, with coefficients for wavelengths
+// in micrometres. When set this replaces `Dielectric::refractive_index` for
+// rays carrying a wavelength.
+#[derive(Copy, Clone)]
+pub enum Dispersion {
+    Cauchy { a: f64, b: f64 },
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn refractive_index(&self, wavelength: f64) -> f64 {
+        let micrometres = wavelength / 1000.0;
+        let l2 = micrometres * micrometres;
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => {
+                let n2 = 1.0 + (0..3).map(|i| b[i] * l2 / (l2 - c[i])).sum::<f64>();
+                n2.sqrt()
+            }
+        }
+    }
+}
+
+fn schlick(cos_theta: f64, refractive_index: f64) -> f64 {
+    let r0 = (1.0 - refractive_index) / (1.0 + refractive_index);
+    let r0 = r0 * r0;
+    r0 + (1.0 - r0) * (1.0 - cos_theta).powf(5.0)
 }
 
 impl Dielectric {
-    fn reflectance(&self, cos_theta: f64) -> f64 {
-        let r0 = (1.0 - self.refractive_index) / (1.0 + self.refractive_index);
-        let r0 = r0 * r0;
-        r0 + (1.0 - r0) * (1.0 - cos_theta).powf(5.0)
+    fn refractive_index_at(&self, wavelength: Option<f64>) -> f64 {
+        match (self.dispersion, wavelength) {
+            (Some(dispersion), Some(wavelength)) => dispersion.refractive_index(wavelength),
+            _ => self.refractive_index,
+        }
     }
 }
 
 impl Scatter for Dielectric {
     fn scatter(&self, rng: &mut SmallRng, ray_in: &Ray, hit: &Hit) -> ScatterResult {
-        let attenuation = Color::new(1.0, 1.0, 1.0);
-        let refraction_ratio = match hit.face {
-            Face::Front => 1.0 / self.refractive_index,
-            Face::Back => self.refractive_index,
+        let refractive_index = self.refractive_index_at(ray_in.wavelength);
+        let (outer_index, inner_index) = match hit.face {
+            Face::Front => (1.0, refractive_index),
+            Face::Back => (refractive_index, 1.0),
         };
+        let refraction_ratio = outer_index / inner_index;
 
         let unit_direction = ray_in.direction;
 
@@ -78,9 +269,43 @@ impl Scatter for Dielectric {
         let can_refract = refraction_ratio * sin_theta <= 1.0;
         // let can_refract = true;
 
-        let reflectance = self.reflectance(cos_theta);
+        let (attenuation, refract) = match self.film {
+            None => {
+                let reflectance = schlick(cos_theta, refractive_index);
+                (
+                    Color::new(1.0, 1.0, 1.0),
+                    can_refract && reflectance <= rng.gen::<f64>(),
+                )
+            }
+            Some(_) if !can_refract => (Color::new(1.0, 1.0, 1.0), false),
+            Some(film) => {
+                let channel = |wavelength| {
+                    film.dielectric_reflectance(cos_theta, outer_index, inner_index, wavelength)
+                };
+                let reflectance = match ray_in.wavelength {
+                    Some(wavelength) => {
+                        let reflectance = channel(wavelength);
+                        Color::new(reflectance, reflectance, reflectance)
+                    }
+                    None => Color::new(
+                        channel(RGB_WAVELENGTHS[0]),
+                        channel(RGB_WAVELENGTHS[1]),
+                        channel(RGB_WAVELENGTHS[2]),
+                    ),
+                };
+                let probability = (reflectance.x + reflectance.y + reflectance.z) / 3.0;
+                if probability <= rng.gen::<f64>() {
+                    (
+                        (Color::new(1.0, 1.0, 1.0) - reflectance) / (1.0 - probability),
+                        true,
+                    )
+                } else {
+                    (reflectance / probability, false)
+                }
+            }
+        };
 
-        let output_direction = if can_refract && reflectance <= rng.gen::<f64>() {
+        let output_direction = if refract {
             unit_direction.refract(hit.normal, refraction_ratio)
         } else {
             unit_direction.reflect(hit.normal)
@@ -88,11 +313,141 @@ impl Scatter for Dielectric {
 
         ScatterResult::Reflect(
             attenuation,
-            Ray::new(hit.point, output_direction.unit_vector()),
+            ray_in.spawn(hit.point, output_direction.unit_vector()),
         )
     }
 }
 
+const MAX_COAT_BOUNCES: usize = 16;
+
+// A dielectric clear coat over an arbitrary base material. Light is either
+// reflected by the coat, or refracted through it to the base and back out,
+// losing energy to absorption in the coat on the way in and out.
+#[derive(Clone)]
+pub struct Coated {
+    pub refractive_index: f64,
+    pub roughness: f64,
+    pub thickness: f64,
+    pub absorption: Color,
+    pub base: Box<Material>,
+}
+
+impl Scatter for Coated {
+    fn scatter(&self, rng: &mut SmallRng, ray_in: &Ray, hit: &Hit) -> ScatterResult {
+        if let Face::Back = hit.face {
+            return self.base.scatter(rng, ray_in, hit);
+        }
+
+        let coat_normal = (hit.normal + self.roughness * random_in_unit_sphere(rng)).unit_vector();
+        let cos_in = ray_in.direction.cos_theta(-coat_normal);
+        if schlick(cos_in, self.refractive_index) > rng.gen::<f64>() {
+            let reflected = ray_in.direction.reflect(coat_normal);
+            return ScatterResult::Reflect(
+                Color::new(1.0, 1.0, 1.0),
+                ray_in.spawn(hit.point, reflected.unit_vector()),
+            );
+        }
+
+        let refracted = ray_in
+            .direction
+            .refract(coat_normal, 1.0 / self.refractive_index)
+            .unit_vector();
+        let cos_refracted = f64::max(refracted.cos_theta(-hit.normal), 1e-3);
+        let mut throughput = transmittance(self.absorption, self.thickness / cos_refracted);
+        let mut ray = ray_in.spawn(hit.point, refracted);
+
+        // Light reflected by the underside of the coat, always so past the
+        // critical angle, goes back down to the base for another bounce.
+        for _ in 0..MAX_COAT_BOUNCES {
+            let (color, ray_out) = match self.base.scatter(rng, &ray, hit) {
+                ScatterResult::Reflect(color, ray_out) => (color, ray_out),
+                ScatterResult::Emit(color) => return ScatterResult::Emit(throughput * color),
+                ScatterResult::Absorb => return ScatterResult::Absorb,
+            };
+
+            let cos_inside = ray_out.direction.cos_theta(hit.normal);
+            if cos_inside <= 0.0 {
+                // Transparent bases pass light on into the object, but from
+                // any other base the ray would go under the surface.
+                return match *self.base {
+                    Material::Dielectric(_) | Material::Subsurface(_) => {
+                        ScatterResult::Reflect(color * throughput, ray_out)
+                    }
+                    _ => ScatterResult::Absorb,
+                };
+            }
+            let crossing =
+                transmittance(self.absorption, self.thickness / f64::max(cos_inside, 1e-3));
+            throughput = throughput * color * crossing;
+
+            let sin_outside = self.refractive_index * ray_out.direction.sin_theta(hit.normal);
+            let reflectance = if sin_outside > 1.0 {
+                1.0
+            } else {
+                schlick(
+                    (1.0 - sin_outside * sin_outside).sqrt(),
+                    self.refractive_index,
+                )
+            };
+            if reflectance > rng.gen::<f64>() {
+                let reflected = ray_out.direction.reflect(hit.normal).unit_vector();
+                throughput = throughput * crossing;
+                ray = ray_out.spawn(hit.point, reflected);
+                continue;
+            }
+
+            let exit_direction = ray_out
+                .direction
+                .refract(-hit.normal, self.refractive_index)
+                .unit_vector();
+            return ScatterResult::Reflect(
+                throughput,
+                ray_out.spawn(ray_out.origin, exit_direction),
+            );
+        }
+        ScatterResult::Absorb
+    }
+}
+
+// A translucent material such as skin, wax or marble. The surface behaves
+// like a smooth dielectric, and light refracted inside takes a random walk
+// through a scattering medium filling the object, with per channel albedo
+// and mean free path.
+#[derive(Copy, Clone)]
+pub struct Subsurface {
+    pub refractive_index: f64,
+    pub albedo: Color,
+    pub mean_free_path: Color,
+    pub g: f64,
+}
+
+impl Subsurface {
+    pub fn interior(&self) -> Homogeneous {
+        let extinction = Color::new(
+            1.0 / self.mean_free_path.x,
+            1.0 / self.mean_free_path.y,
+            1.0 / self.mean_free_path.z,
+        );
+        let scattering = self.albedo * extinction;
+        Homogeneous {
+            absorption: extinction - scattering,
+            scattering,
+            phase: HenyeyGreenstein { g: self.g },
+        }
+    }
+}
+
+impl Scatter for Subsurface {
+    fn scatter(&self, rng: &mut SmallRng, ray_in: &Ray, hit: &Hit) -> ScatterResult {
+        let boundary = Dielectric {
+            refractive_index: self.refractive_index,
+            film: None,
+            dispersion: None,
+        };
+        boundary.scatter(rng, ray_in, hit)
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Light {
     pub color: Color,
@@ -104,20 +459,46 @@ impl Scatter for Light {
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Clone)]
 pub enum Material {
     Diffuse(Diffuse),
+    OrenNayar(OrenNayar),
     Metal(Metal),
     Dielectric(Dielectric),
-    Light(Light)
+    Coated(Coated),
+    Subsurface(Subsurface),
+    Light(Light),
+}
+
+impl Material {
+    // The medium filling the inside of objects made of this material.
+    pub fn interior(&self) -> Option<Homogeneous> {
+        match self {
+            Material::Subsurface(material) => Some(material.interior()),
+            _ => None,
+        }
+    }
+
+    // Whether the material bends light by wavelength, which only rays
+    // carrying a wavelength can follow.
+    pub fn is_dispersive(&self) -> bool {
+        match self {
+            Material::Dielectric(material) => material.dispersion.is_some(),
+            Material::Coated(material) => material.base.is_dispersive(),
+            _ => false,
+        }
+    }
 }
 
 impl Scatter for Material {
     fn scatter(&self, rng: &mut SmallRng, ray_in: &Ray, hit: &Hit) -> ScatterResult {
         match &self {
             Material::Diffuse(material) => material.scatter(rng, ray_in, hit),
+            Material::OrenNayar(material) => material.scatter(rng, ray_in, hit),
             Material::Metal(material) => material.scatter(rng, ray_in, hit),
             Material::Dielectric(material) => material.scatter(rng, ray_in, hit),
+            Material::Coated(material) => material.scatter(rng, ray_in, hit),
+            Material::Subsurface(material) => material.scatter(rng, ray_in, hit),
             Material::Light(material) => material.scatter(rng, ray_in, hit),
         }
     }
@@ -0,0 +1,141 @@
+use super::hittable::Hittable;
+use super::spectrum::at_wavelength;
+use super::vec3::{Color, Onb, Ray, UnitVec3, Vec3};
+use rand::{rngs::SmallRng, Rng};
+use std::f64::consts::PI;
+
+pub fn transmittance(extinction: Color, distance: f64) -> Color {
+    Color::new(
+        (-extinction.x * distance).exp(),
+        (-extinction.y * distance).exp(),
+        (-extinction.z * distance).exp(),
+    )
+}
+
+fn channel(color: Color, index: usize) -> f64 {
+    match index {
+        0 => color.x,
+        1 => color.y,
+        _ => color.z,
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct HenyeyGreenstein {
+    pub g: f64,
+}
+
+impl HenyeyGreenstein {
+    // Sampling is exact, so the phase function needs no weight.
+    pub fn sample(&self, rng: &mut SmallRng, direction: UnitVec3) -> UnitVec3 {
+        let g = self.g;
+        let xi = rng.gen::<f64>();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            (1.0 + g * g - s * s) / (2.0 * g)
+        };
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.gen::<f64>();
+
+        Onb::from_w(direction)
+            .local(Vec3::new(
+                sin_theta * phi.cos(),
+                sin_theta * phi.sin(),
+                cos_theta,
+            ))
+            .unit_vector()
+    }
+}
+
+pub enum MediumSample {
+    Scatter {
+        t: f64,
+        weight: Color,
+        emission: Color,
+        phase: HenyeyGreenstein,
+    },
+    Pass(Color),
+}
+
+pub trait Medium: Send + Sync {
+    // Sample a free flight along the ray between `t_min` and `t_max`.
+    fn sample(&self, rng: &mut SmallRng, ray: &Ray, t_min: f64, t_max: f64) -> MediumSample;
+
+    // Estimate the fraction of light getting through between `t_min` and
+    // `t_max`, for media the world passes a ray through while another one
+    // samples the free flight.
+    fn transmittance(&self, rng: &mut SmallRng, ray: &Ray, t_min: f64, t_max: f64) -> Color;
+}
+
+#[derive(Copy, Clone)]
+pub struct Homogeneous {
+    pub absorption: Color,
+    pub scattering: Color,
+    pub phase: HenyeyGreenstein,
+}
+
+impl Medium for Homogeneous {
+    fn sample(&self, rng: &mut SmallRng, ray: &Ray, t_min: f64, t_max: f64) -> MediumSample {
+        let scattering = at_wavelength(self.scattering, ray.wavelength);
+        let extinction = at_wavelength(self.absorption, ray.wavelength) + scattering;
+
+        // Pick a channel to sample the distance from, and weight by the
+        // average pdf over all channels.
+        let sigma = channel(extinction, rng.gen_range(0, 3));
+        let distance = if sigma > 0.0 {
+            -(1.0 - rng.gen::<f64>()).ln() / sigma
+        } else {
+            f64::INFINITY
+        };
+
+        let length = t_max - t_min;
+        let scattered = distance < length;
+        let distance = distance.min(length);
+        let tr = transmittance(extinction, distance);
+
+        let density = if scattered { extinction * tr } else { tr };
+        let pdf = (density.x + density.y + density.z) / 3.0;
+        if pdf <= 0.0 {
+            return MediumSample::Pass(Color::new(0.0, 0.0, 0.0));
+        }
+
+        if scattered {
+            MediumSample::Scatter {
+                t: t_min + distance,
+                weight: tr * scattering / pdf,
+                emission: Color::new(0.0, 0.0, 0.0),
+                phase: self.phase,
+            }
+        } else {
+            MediumSample::Pass(tr / pdf)
+        }
+    }
+
+    fn transmittance(&self, _rng: &mut SmallRng, ray: &Ray, t_min: f64, t_max: f64) -> Color {
+        let extinction = at_wavelength(self.absorption + self.scattering, ray.wavelength);
+        transmittance(extinction, t_max - t_min)
+    }
+}
+
+// A medium filling the inside of a closed convex boundary.
+pub struct Volume {
+    pub boundary: Box<dyn Hittable + Send + Sync>,
+    pub medium: Box<dyn Medium>,
+}
+
+impl Volume {
+    pub fn segment(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let enter = self.boundary.hit(ray, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hit(ray, enter.t + 0.0001, f64::INFINITY)?;
+
+        let t0 = enter.t.max(t_min);
+        let t1 = exit.t.min(t_max);
+        if t0 < t1 {
+            Some((t0, t1))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use super::camera::{Angle, Projection, Shutter, Stereo};
+use super::vec3::{CrossProduct, Point3, Ray, UnitVec3, Vec3};
+use rand::rngs::SmallRng;
+use std::f64::consts::PI;
+
+// The position and orientation shared by the panoramic cameras. `u` points
+// right, `v` up and `w` back, away from `look_at`.
+#[derive(Copy, Clone)]
+pub struct Frame {
+    pub origin: Point3,
+    pub u: UnitVec3,
+    pub v: UnitVec3,
+    pub w: UnitVec3,
+}
+
+impl Frame {
+    pub fn new(look_from: Point3, look_at: Point3, vup: Vec3) -> Self {
+        let w = (look_from - look_at).unit_vector();
+        let u = vup.cross(w).unit_vector();
+        let v = w.cross(u);
+        Frame {
+            origin: look_from,
+            u,
+            v,
+            w,
+        }
+    }
+
+    // A ray from the origin along `(x, y, z)` in the frame's coordinates.
+    fn ray(&self, rng: &mut SmallRng, shutter: &Shutter, x: f64, y: f64, z: f64) -> Ray {
+        let direction = x * self.u + y * self.v + z * self.w;
+        Ray::new(self.origin, direction.unit_vector()).with_time(shutter.sample(rng))
+    }
+}
+
+// A full sphere of directions, with longitude across the image and latitude
+// up it. The center of the image looks at `look_at`. Images should be twice
+// as wide as they are high.
+//
+// For omni-directional stereo, rays start `offset` to the right of the
+// origin, at right angles to each direction in the horizontal plane, and turn
+// in to meet at `convergence`.
+#[derive(Copy, Clone)]
+pub struct Equirectangular {
+    pub frame: Frame,
+    pub offset: f64,
+    pub convergence: f64,
+    pub shutter: Shutter,
+}
+
+impl Equirectangular {
+    pub fn new(frame: Frame) -> Self {
+        Equirectangular {
+            frame,
+            offset: 0.0,
+            convergence: f64::INFINITY,
+            shutter: Shutter::default(),
+        }
+    }
+}
+
+impl Stereo for Equirectangular {
+    fn shifted(&self, offset: f64, convergence: f64) -> Self {
+        Equirectangular {
+            offset: self.offset + offset,
+            convergence,
+            ..*self
+        }
+    }
+}
+
+impl Projection for Equirectangular {
+    fn get_ray(&self, rng: &mut SmallRng, s: f64, t: f64) -> Option<Ray> {
+        let longitude = (s - 0.5) * 2.0 * PI;
+        let latitude = (t - 0.5) * PI;
+        let (sin, cos) = longitude.sin_cos();
+        let frame = &self.frame;
+        let direction: Vec3 =
+            latitude.cos() * (sin * frame.u - cos * frame.w) + latitude.sin() * frame.v;
+        let side = self.offset * (cos * frame.u + sin * frame.w);
+        let direction = if self.convergence.is_finite() {
+            self.convergence * direction - side
+        } else {
+            direction
+        };
+        Some(
+            Ray::new(frame.origin + side, direction.unit_vector())
+                .with_time(self.shutter.sample(rng)),
+        )
+    }
+}
+
+// The six faces of a cube around the camera, laid out in a 3 by 2 grid:
+// +x, -x, +y along the top and -y, +z, -z along the bottom, in the frame's
+// coordinates, so the camera looks through -z. Side faces have +y up, and
+// the +y and -y faces are turned so their bottom and top edges meet -z.
+// Images should have a 3:2 aspect ratio.
+pub struct Cubemap {
+    pub frame: Frame,
+    pub shutter: Shutter,
+}
+
+impl Cubemap {
+    pub fn new(frame: Frame) -> Self {
+        Cubemap {
+            frame,
+            shutter: Shutter::default(),
+        }
+    }
+}
+
+// For each face: the direction through its center, and the directions of
+// its right and up edges.
+const CUBE_FACES: [[[f64; 3]; 3]; 6] = [
+    [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+    [[-1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]],
+    [[0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+    [[0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]],
+    [[0.0, 0.0, 1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+    [[0.0, 0.0, -1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+];
+
+impl Projection for Cubemap {
+    fn get_ray(&self, rng: &mut SmallRng, s: f64, t: f64) -> Option<Ray> {
+        let column = ((s * 3.0) as usize).min(2);
+        let row = (((1.0 - t) * 2.0) as usize).min(1);
+        let [center, right, up] = CUBE_FACES[3 * row + column];
+
+        // Coordinates across the face, from -1 to 1.
+        let a = 2.0 * (s * 3.0 - column as f64) - 1.0;
+        let b = 2.0 * (t * 2.0 - (1 - row) as f64) - 1.0;
+        let d = |i: usize| center[i] + a * right[i] + b * up[i];
+        Some(self.frame.ray(rng, &self.shutter, d(0), d(1), d(2)))
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum FisheyeMapping {
+    // Distance from the image center proportional to the angle off axis.
+    Equidistant,
+    // Equal areas of the image cover equal solid angles.
+    Equisolid,
+}
+
+// A circular fisheye image: a circle touching the top and bottom of the image
+// covers `fov`, which may be up to 360 degrees. The rest of the image is
+// black.
+pub struct Fisheye {
+    pub frame: Frame,
+    pub fov: Angle,
+    pub mapping: FisheyeMapping,
+    pub aspect_ratio: f64,
+    pub shutter: Shutter,
+}
+
+impl Fisheye {
+    pub fn new(frame: Frame, fov: Angle, mapping: FisheyeMapping, aspect_ratio: f64) -> Self {
+        Fisheye {
+            frame,
+            fov,
+            mapping,
+            aspect_ratio,
+            shutter: Shutter::default(),
+        }
+    }
+}
+
+impl Projection for Fisheye {
+    fn get_ray(&self, rng: &mut SmallRng, s: f64, t: f64) -> Option<Ray> {
+        let x = (2.0 * s - 1.0) * self.aspect_ratio;
+        let y = 2.0 * t - 1.0;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+
+        let half_fov = self.fov.radians / 2.0;
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * half_fov,
+            FisheyeMapping::Equisolid => 2.0 * (r * (half_fov / 2.0).sin()).asin(),
+        };
+        let (x, y) = if r > 0.0 { (x / r, y / r) } else { (0.0, 0.0) };
+        let sin = theta.sin();
+        Some(
+            self.frame
+                .ray(rng, &self.shutter, sin * x, sin * y, -theta.cos()),
+        )
+    }
+}
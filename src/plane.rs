@@ -0,0 +1,95 @@
+use super::hittable::{Hit, Hittable};
+use super::material::Material;
+use super::vec3::{Onb, Point3, Ray, Vec3};
+use super::world::Object;
+
+fn intersect(ray: &Ray, point: Point3, frame: &Onb, t_min: f64, t_max: f64) -> Option<f64> {
+    let denominator = frame.w.cos_theta(ray.direction);
+    if denominator.abs() < 1e-8 {
+        return None;
+    }
+
+    let t = Vec3::dot(point - ray.origin, frame.w.into()) / denominator;
+    if t < t_min || t > t_max {
+        None
+    } else {
+        Some(t)
+    }
+}
+
+// An infinite plane through `point`. UV coordinates tile once per unit of
+// distance along the plane.
+pub struct Plane {
+    point: Point3,
+    frame: Onb,
+    pub material: Material,
+}
+
+impl Plane {
+    pub fn new(point: Point3, normal: Vec3, material: &Material) -> Self {
+        Plane {
+            point,
+            frame: Onb::from_w(normal.unit_vector()),
+            material: material.clone(),
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let t = intersect(ray, self.point, &self.frame, t_min, t_max)?;
+        let point = ray.at(t);
+        let offset = point - self.point;
+        let u = Vec3::dot(offset, self.frame.u.into()).rem_euclid(1.0);
+        let v = Vec3::dot(offset, self.frame.v.into()).rem_euclid(1.0);
+
+        Some(Hit::new(t, ray, point, self.frame.w, (u, v)))
+    }
+}
+
+impl Object for Plane {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+// A flat disk, with UV coordinates mapped across its bounding square.
+pub struct Disk {
+    center: Point3,
+    frame: Onb,
+    radius: f64,
+    pub material: Material,
+}
+
+impl Disk {
+    pub fn new(center: Point3, normal: Vec3, radius: f64, material: &Material) -> Self {
+        Disk {
+            center,
+            frame: Onb::from_w(normal.unit_vector()),
+            radius,
+            material: material.clone(),
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let t = intersect(ray, self.center, &self.frame, t_min, t_max)?;
+        let point = ray.at(t);
+        let offset = point - self.center;
+        if offset.length_squared() > self.radius * self.radius {
+            return None;
+        }
+
+        let u = 0.5 * (Vec3::dot(offset, self.frame.u.into()) / self.radius + 1.0);
+        let v = 0.5 * (Vec3::dot(offset, self.frame.v.into()) / self.radius + 1.0);
+
+        Some(Hit::new(t, ray, point, self.frame.w, (u, v)))
+    }
+}
+
+impl Object for Disk {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
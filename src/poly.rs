@@ -0,0 +1,89 @@
+// Polynomials in one variable, with coefficients stored lowest degree first.
+
+pub fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |acc, &c| acc * x + c)
+}
+
+pub fn multiply(a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut result = vec![0.0; a.len() + b.len() - 1];
+    for (i, &x) in a.iter().enumerate() {
+        for (j, &y) in b.iter().enumerate() {
+            result[i + j] += x * y;
+        }
+    }
+    result
+}
+
+fn derivative(coefficients: &[f64]) -> Vec<f64> {
+    coefficients
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, &c)| i as f64 * c)
+        .collect()
+}
+
+fn bisect(coefficients: &[f64], mut low: f64, mut high: f64) -> f64 {
+    let mut f_low = evaluate(coefficients, low);
+    for _ in 0..100 {
+        let mid = 0.5 * (low + high);
+        if mid <= low || mid >= high {
+            break;
+        }
+        let f_mid = evaluate(coefficients, mid);
+        if (f_mid < 0.0) == (f_low < 0.0) {
+            low = mid;
+            f_low = f_mid;
+        } else {
+            high = mid;
+        }
+    }
+    0.5 * (low + high)
+}
+
+// The real roots in [min, max], in increasing order. The roots of the
+// derivative split the range into pieces on which the polynomial is
+// monotonic, so each piece holds at most one root and can be bisected
+// safely. Roots where the polynomial only touches zero are not found.
+pub fn real_roots(coefficients: &[f64], min: f64, max: f64) -> Vec<f64> {
+    let scale = coefficients
+        .iter()
+        .fold(0.0, |acc: f64, c| acc.max(c.abs()));
+    let mut degree = coefficients.len().saturating_sub(1);
+    while degree > 0 && coefficients[degree].abs() <= 1e-14 * scale {
+        degree -= 1;
+    }
+    let coefficients = &coefficients[..=degree];
+
+    if degree == 0 {
+        return vec![];
+    }
+    if degree == 1 {
+        let root = -coefficients[0] / coefficients[1];
+        return if root >= min && root <= max {
+            vec![root]
+        } else {
+            vec![]
+        };
+    }
+
+    let mut bounds = vec![min];
+    bounds.extend(real_roots(&derivative(coefficients), min, max));
+    bounds.push(max);
+
+    let mut roots = vec![];
+    for pair in bounds.windows(2) {
+        let (low, high) = (pair[0], pair[1]);
+        let (f_low, f_high) = (evaluate(coefficients, low), evaluate(coefficients, high));
+        if f_low == 0.0 {
+            if roots.last() != Some(&low) {
+                roots.push(low);
+            }
+        } else if f_high == 0.0 {
+            roots.push(high);
+        } else if (f_low < 0.0) != (f_high < 0.0) {
+            roots.push(bisect(coefficients, low, high));
+        }
+    }
+    roots
+}
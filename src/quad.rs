@@ -0,0 +1,177 @@
+use super::hittable::{Crossing, Hit, Hittable, Interval, Solid};
+use super::material::Material;
+use super::vec3::{CrossProduct, Point3, Ray, UnitVec3, Vec3};
+use super::world::Object;
+
+// A parallelogram with corner `q` and edges `u` and `v`. The outward normal
+// is along `u x v`, and the hit's UV coordinates run along the two edges.
+pub struct Quad {
+    q: Point3,
+    u: Vec3,
+    v: Vec3,
+    pub material: Material,
+    normal: UnitVec3,
+    d: f64,
+    w: Vec3,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Vec3, v: Vec3, material: &Material) -> Self {
+        let n = u.cross(v);
+        let normal = n.unit_vector();
+        Quad {
+            q,
+            u,
+            v,
+            material: material.clone(),
+            normal,
+            d: Vec3::dot(normal.into(), q),
+            w: n / n.length_squared(),
+        }
+    }
+
+    // Axis aligned rectangles at `k` on the remaining axis, facing along
+    // that axis.
+    pub fn xy(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, material: &Material) -> Self {
+        Quad::new(
+            Point3::new(x0, y0, k),
+            Vec3::new(x1 - x0, 0.0, 0.0),
+            Vec3::new(0.0, y1 - y0, 0.0),
+            material,
+        )
+    }
+
+    pub fn xz(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, material: &Material) -> Self {
+        Quad::new(
+            Point3::new(x0, k, z0),
+            Vec3::new(0.0, 0.0, z1 - z0),
+            Vec3::new(x1 - x0, 0.0, 0.0),
+            material,
+        )
+    }
+
+    pub fn yz(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, material: &Material) -> Self {
+        Quad::new(
+            Point3::new(k, y0, z0),
+            Vec3::new(0.0, y1 - y0, 0.0),
+            Vec3::new(0.0, 0.0, z1 - z0),
+            material,
+        )
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let denominator = self.normal.cos_theta(ray.direction);
+        if denominator.abs() < 1e-8 {
+            return None;
+        }
+
+        let t = (self.d - Vec3::dot(self.normal.into(), ray.origin)) / denominator;
+        if t < t_min || t > t_max {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let planar = point - self.q;
+        let alpha = Vec3::dot(self.w, planar.cross(self.v));
+        let beta = Vec3::dot(self.w, self.u.cross(planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        Some(Hit::new(t, ray, point, self.normal, (alpha, beta)))
+    }
+}
+
+impl Object for Quad {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+// A box made of six quads with outward facing normals.
+pub struct Cuboid {
+    sides: Vec<Quad>,
+    pub material: Material,
+}
+
+impl Cuboid {
+    // An axis aligned box with opposite corners `a` and `b`.
+    pub fn new(a: Point3, b: Point3, material: &Material) -> Self {
+        let min = Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+        let max = Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+        let size = max - min;
+        Cuboid::from_edges(
+            min,
+            [
+                Vec3::new(size.x, 0.0, 0.0),
+                Vec3::new(0.0, size.y, 0.0),
+                Vec3::new(0.0, 0.0, size.z),
+            ],
+            material,
+        )
+    }
+
+    // An arbitrarily oriented box with a corner and three edges leaving it.
+    pub fn from_edges(corner: Point3, edges: [Vec3; 3], material: &Material) -> Self {
+        let [mut x, mut y, z] = edges;
+        if Vec3::dot(x.cross(y), z) < 0.0 {
+            std::mem::swap(&mut x, &mut y);
+        }
+
+        let sides = vec![
+            Quad::new(corner + z, x, y, material),
+            Quad::new(corner, y, x, material),
+            Quad::new(corner + x, y, z, material),
+            Quad::new(corner, z, y, material),
+            Quad::new(corner + y, z, x, material),
+            Quad::new(corner, x, z, material),
+        ];
+
+        Cuboid {
+            sides,
+            material: material.clone(),
+        }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let mut closest_so_far = t_max;
+        let mut best_so_far = None;
+        for side in &self.sides {
+            if let Some(hit) = side.hit(ray, t_min, closest_so_far) {
+                closest_so_far = hit.t;
+                best_so_far = Some(hit);
+            }
+        }
+        best_so_far
+    }
+}
+
+impl Solid for Cuboid {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let mut crossings: Vec<Crossing> = self
+            .sides
+            .iter()
+            .filter_map(|side| side.hit(ray, f64::NEG_INFINITY, f64::INFINITY))
+            .map(|hit| Crossing::from_hit(&hit))
+            .collect();
+        if crossings.len() < 2 {
+            return vec![];
+        }
+        crossings.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap());
+
+        vec![Interval {
+            enter: crossings[0],
+            exit: crossings[crossings.len() - 1],
+        }]
+    }
+}
+
+impl Object for Cuboid {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
@@ -48,3 +48,11 @@ pub fn random_vec3_range<R: Rng>(rng: &mut R, min: f64, max: f64) -> Vec3 {
 pub fn random_f64_range<R: Rng>(rng: &mut R, min: f64, max: f64) -> f64 {
     rng.gen_range(min, max)
 }
+
+pub fn random_cosine_direction<R: Rng>(rng: &mut R) -> Vec3 {
+    let r1 = rng.gen::<f64>();
+    let r2 = rng.gen::<f64>();
+    let phi = 2.0 * std::f64::consts::PI * r1;
+    let sqrt_r2 = r2.sqrt();
+    Vec3::new(phi.cos() * sqrt_r2, phi.sin() * sqrt_r2, (1.0 - r2).sqrt())
+}
@@ -0,0 +1,197 @@
+use super::hittable::{Hit, Hittable};
+use super::material::Material;
+use super::torus::bounds;
+use super::vec3::{Point3, Ray, Vec3};
+use super::world::Object;
+use std::f64::consts::PI;
+
+const MAX_STEPS: usize = 512;
+const HIT_DISTANCE: f64 = 1e-4;
+const GRADIENT_STEP: f64 = 1e-4;
+
+// A signed distance function: negative inside the shape, positive outside.
+// Primitives are centered on the origin, with tori around the y axis.
+pub enum Sdf {
+    Sphere {
+        radius: f64,
+    },
+    Cuboid {
+        half_size: Vec3,
+    },
+    RoundedCuboid {
+        half_size: Vec3,
+        radius: f64,
+    },
+    Torus {
+        major_radius: f64,
+        minor_radius: f64,
+    },
+    Translate {
+        offset: Vec3,
+        inner: Box<Sdf>,
+    },
+    // Union with the seam blended over a distance of about `smoothness`.
+    SmoothUnion {
+        a: Box<Sdf>,
+        b: Box<Sdf>,
+        smoothness: f64,
+    },
+    // `a` with `b` cut out of it.
+    Subtraction {
+        a: Box<Sdf>,
+        b: Box<Sdf>,
+    },
+    // Rotates slices of the shape around the y axis by `rate` radians per
+    // unit of height. The result is no longer an exact distance, so objects
+    // using it need a smaller step scale.
+    Twist {
+        rate: f64,
+        inner: Box<Sdf>,
+    },
+    // Repeats the shape on a grid with the given spacing. Axes with a spacing
+    // of zero are not repeated.
+    Repeat {
+        spacing: Vec3,
+        inner: Box<Sdf>,
+    },
+}
+
+fn cuboid_distance(p: Vec3, half_size: Vec3) -> f64 {
+    let q = Vec3::new(
+        p.x.abs() - half_size.x,
+        p.y.abs() - half_size.y,
+        p.z.abs() - half_size.z,
+    );
+    let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+    let inside = q.x.max(q.y).max(q.z).min(0.0);
+    outside + inside
+}
+
+fn repeat(x: f64, spacing: f64) -> f64 {
+    if spacing == 0.0 {
+        x
+    } else {
+        x - spacing * (x / spacing).round()
+    }
+}
+
+impl Sdf {
+    pub fn distance(&self, p: Vec3) -> f64 {
+        match self {
+            Sdf::Sphere { radius } => p.length() - radius,
+            Sdf::Cuboid { half_size } => cuboid_distance(p, *half_size),
+            Sdf::RoundedCuboid { half_size, radius } => {
+                let core = *half_size - Vec3::new(*radius, *radius, *radius);
+                cuboid_distance(p, core) - radius
+            }
+            Sdf::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - major_radius;
+                (ring * ring + p.y * p.y).sqrt() - minor_radius
+            }
+            Sdf::Translate { offset, inner } => inner.distance(p - *offset),
+            Sdf::SmoothUnion { a, b, smoothness } => {
+                let (da, db) = (a.distance(p), b.distance(p));
+                let h = (0.5 + 0.5 * (db - da) / smoothness).clamp(0.0, 1.0);
+                db + (da - db) * h - smoothness * h * (1.0 - h)
+            }
+            Sdf::Subtraction { a, b } => a.distance(p).max(-b.distance(p)),
+            Sdf::Twist { rate, inner } => {
+                let (sin, cos) = (rate * p.y).sin_cos();
+                inner.distance(Vec3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z))
+            }
+            Sdf::Repeat { spacing, inner } => inner.distance(Vec3::new(
+                repeat(p.x, spacing.x),
+                repeat(p.y, spacing.y),
+                repeat(p.z, spacing.z),
+            )),
+        }
+    }
+
+    // The gradient of the distance by central differences.
+    pub fn gradient(&self, p: Vec3) -> Vec3 {
+        let axis = |offset: Vec3| {
+            (self.distance(p + offset) - self.distance(p - offset)) / (2.0 * GRADIENT_STEP)
+        };
+        Vec3::new(
+            axis(Vec3::new(GRADIENT_STEP, 0.0, 0.0)),
+            axis(Vec3::new(0.0, GRADIENT_STEP, 0.0)),
+            axis(Vec3::new(0.0, 0.0, GRADIENT_STEP)),
+        )
+    }
+}
+
+// A shape given by a signed distance function around `center`, rendered by
+// sphere tracing: the ray advances by the distance to the surface until it is
+// close enough to count as a hit. Only the part of the shape inside the
+// sphere of `bound_radius` around the center is rendered. Functions that
+// overestimate the distance need a `step_scale` below one.
+pub struct SdfObject {
+    pub sdf: Sdf,
+    pub center: Point3,
+    pub bound_radius: f64,
+    pub step_scale: f64,
+    pub material: Material,
+}
+
+impl SdfObject {
+    pub fn new(sdf: Sdf, center: Point3, bound_radius: f64, material: &Material) -> Self {
+        SdfObject {
+            sdf,
+            center,
+            bound_radius,
+            step_scale: 1.0,
+            material: material.clone(),
+        }
+    }
+
+    pub fn with_step_scale(self, step_scale: f64) -> Self {
+        SdfObject { step_scale, ..self }
+    }
+}
+
+impl Hittable for SdfObject {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let o = ray.origin - self.center;
+        let d: Vec3 = ray.direction.into();
+        let (t0, t1) = bounds(o, d, self.bound_radius, t_min, t_max)?;
+
+        // Rays starting on the surface, such as those leaving it at a grazing
+        // angle, first move off it so they don't hit where they started.
+        let mut t = (0..MAX_STEPS)
+            .map(|i| t0 + i as f64 * HIT_DISTANCE)
+            .find(|&t| self.sdf.distance(o + t * d).abs() >= HIT_DISTANCE)
+            .filter(|&t| t <= t1)?;
+
+        // Rays starting inside the shape march on the negated distance, so
+        // they stop where they leave it.
+        let sign = self.sdf.distance(o + t * d).signum();
+        for _ in 0..MAX_STEPS {
+            let distance = sign * self.sdf.distance(o + t * d);
+            if distance < HIT_DISTANCE {
+                let p = o + t * d;
+                let normal = self.sdf.gradient(p);
+                if normal.near_zero() {
+                    return None;
+                }
+                let direction = p / self.bound_radius;
+                let u = ((-direction.z).atan2(direction.x) + PI) / (2.0 * PI);
+                let v = (-direction.y).clamp(-1.0, 1.0).acos() / PI;
+                return Some(Hit::new(t, ray, ray.at(t), normal.unit_vector(), (u, v)));
+            }
+            t += self.step_scale * distance;
+            if t > t1 {
+                return None;
+            }
+        }
+        None
+    }
+}
+
+impl Object for SdfObject {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
@@ -0,0 +1,125 @@
+use super::vec3::{Color, Vec3};
+use rand::Rng;
+
+pub const LAMBDA_MIN: f64 = 380.0;
+pub const LAMBDA_MAX: f64 = 780.0;
+
+// Integral of the fitted CIE y-bar curve over the visible range.
+const CIE_Y_INTEGRAL: f64 = 106.919_734_6;
+
+// Von Kries style scale in linear sRGB taking the equal energy white point to
+// D65, so a flat unit spectrum renders as white.
+const WHITE_BALANCE: [f64; 3] = [0.832_961_07, 1.053_001_33, 1.101_529_43];
+
+pub fn sample_wavelength<R: Rng>(rng: &mut R) -> f64 {
+    LAMBDA_MIN + (LAMBDA_MAX - LAMBDA_MIN) * rng.gen::<f64>()
+}
+
+fn piecewise_gaussian(x: f64, mu: f64, sigma_low: f64, sigma_high: f64) -> f64 {
+    let sigma = if x < mu { sigma_low } else { sigma_high };
+    let t = (x - mu) / sigma;
+    (-0.5 * t * t).exp()
+}
+
+// Multi-lobe fit of the CIE 1931 colour matching functions from Wyman, Sloan
+// and Shirley, "Simple Analytic Approximations to the CIE XYZ Color Matching
+// Functions".
+pub fn cie_xyz(lambda: f64) -> Vec3 {
+    Vec3::new(
+        1.056 * piecewise_gaussian(lambda, 599.8, 37.9, 31.0)
+            + 0.362 * piecewise_gaussian(lambda, 442.0, 16.0, 26.7)
+            - 0.065 * piecewise_gaussian(lambda, 501.1, 20.4, 26.2),
+        0.821 * piecewise_gaussian(lambda, 568.8, 46.9, 40.5)
+            + 0.286 * piecewise_gaussian(lambda, 530.9, 16.3, 31.1),
+        1.217 * piecewise_gaussian(lambda, 437.0, 11.8, 36.0)
+            + 0.681 * piecewise_gaussian(lambda, 459.0, 26.0, 13.8),
+    )
+}
+
+pub fn xyz_to_linear_srgb(xyz: Vec3) -> Color {
+    Color::new(
+        3.240_454_2 * xyz.x - 1.537_138_5 * xyz.y - 0.498_531_4 * xyz.z,
+        -0.969_266_0 * xyz.x + 1.876_010_8 * xyz.y + 0.041_556_0 * xyz.z,
+        0.055_643_4 * xyz.x - 0.204_025_9 * xyz.y + 1.057_225_2 * xyz.z,
+    )
+}
+
+pub fn xyz_to_rgb(xyz: Vec3) -> Color {
+    let rgb = xyz_to_linear_srgb(xyz);
+    Color::new(
+        rgb.x * WHITE_BALANCE[0],
+        rgb.y * WHITE_BALANCE[1],
+        rgb.z * WHITE_BALANCE[2],
+    )
+}
+
+// Estimate of XYZ from a radiance sample at a single uniformly sampled
+// wavelength, normalised so a flat unit spectrum has Y = 1.
+pub fn spectral_sample_to_xyz(lambda: f64, radiance: f64) -> Vec3 {
+    (radiance * (LAMBDA_MAX - LAMBDA_MIN) / CIE_Y_INTEGRAL) * cie_xyz(lambda)
+}
+
+fn logistic(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+// Upsample an RGB colour to a smooth spectrum and evaluate it at `lambda`.
+// The red, green and blue bands sum to one everywhere, so grey values map to
+// flat spectra and reflectances in [0, 1] stay in [0, 1].
+pub fn rgb_to_spectrum(rgb: Color, lambda: f64) -> f64 {
+    let blue = logistic((490.0 - lambda) / 10.0);
+    let red = logistic((lambda - 590.0) / 10.0);
+    let green = 1.0 - blue - red;
+    rgb.x * red + rgb.y * green + rgb.z * blue
+}
+
+// The colour as seen by a ray: unchanged for RGB rays, or the upsampled
+// spectrum at the ray's wavelength for spectral rays.
+pub fn at_wavelength(color: Color, wavelength: Option<f64>) -> Color {
+    match wavelength {
+        Some(lambda) => {
+            let value = rgb_to_spectrum(color, lambda);
+            Color::new(value, value, value)
+        }
+        None => color,
+    }
+}
+
+// Planck's law, spectral radiance of a black body at `temperature` kelvin.
+pub fn blackbody(temperature: f64, lambda: f64) -> f64 {
+    const H: f64 = 6.626_070_15e-34;
+    const C: f64 = 299_792_458.0;
+    const K: f64 = 1.380_649e-23;
+
+    let l = lambda * 1e-9;
+    let exponent = H * C / (l * K * temperature);
+    // Cold bodies give off next to nothing at visible wavelengths, where the
+    // exponential would overflow.
+    if temperature <= 0.0 || exponent > 700.0 {
+        return 0.0;
+    }
+    2.0 * H * C * C / (l.powi(5) * exponent.exp_m1())
+}
+
+// XYZ of a black body at `temperature` kelvin, with Y its luminance in cd/m^2.
+pub fn blackbody_xyz(temperature: f64) -> Vec3 {
+    const STEPS: usize = 80;
+    // Lumens per watt at the peak of the y-bar curve.
+    const LUMINOUS_EFFICACY: f64 = 683.0;
+
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / STEPS as f64;
+    let xyz: Vec3 = (0..STEPS)
+        .map(|i| {
+            let lambda = LAMBDA_MIN + (i as f64 + 0.5) * step;
+            blackbody(temperature, lambda) * cie_xyz(lambda)
+        })
+        .sum();
+    LUMINOUS_EFFICACY * step * 1e-9 * xyz
+}
+
+// Colour of a black body at `temperature` kelvin, as radiance in cd/m^2. The
+// deep reds of cool bodies lie outside the RGB gamut, and are clipped to it.
+pub fn blackbody_rgb(temperature: f64) -> Color {
+    let rgb = xyz_to_rgb(blackbody_xyz(temperature));
+    Color::new(rgb.x.max(0.0), rgb.y.max(0.0), rgb.z.max(0.0))
+}
@@ -0,0 +1,197 @@
+use super::hittable::{Hit, Hittable};
+use super::material::Material;
+use super::poly::{multiply, real_roots};
+use super::vec3::{Point3, Ray, Vec3};
+use super::world::Object;
+use std::f64::consts::PI;
+
+// The part of the ray between `t_min` and `t_max` inside a bounding sphere.
+pub(crate) fn bounds(
+    origin: Vec3,
+    direction: Vec3,
+    radius: f64,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64)> {
+    let half_b = Vec3::dot(origin, direction);
+    let c = origin.length_squared() - radius * radius;
+    let discriminant = half_b * half_b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrtd = discriminant.sqrt();
+    let t0 = (-half_b - sqrtd).max(t_min);
+    let t1 = (-half_b + sqrtd).min(t_max);
+    if t0 < t1 {
+        Some((t0, t1))
+    } else {
+        None
+    }
+}
+
+// Solve for the first root of the ray polynomial in [t0, t1]. The polynomial
+// is built by `coefficients` about the start of the range, which keeps the
+// quartic well conditioned for distant ray origins.
+fn first_root<F>(
+    origin: Vec3,
+    direction: Vec3,
+    (t0, t1): (f64, f64),
+    coefficients: F,
+) -> Option<f64>
+where
+    F: Fn(Vec3) -> Vec<f64>,
+{
+    let start = origin + t0 * direction;
+    let roots = real_roots(&coefficients(start), 0.0, t1 - t0);
+    roots.first().map(|s| t0 + s)
+}
+
+// A torus around the y axis through `center`. `major_radius` is the distance
+// from the center to the middle of the tube, and `minor_radius` the radius of
+// the tube. UV coordinates run around the axis and around the tube.
+pub struct Torus {
+    pub center: Point3,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    pub material: Material,
+}
+
+impl Torus {
+    fn coefficients(&self, o: Vec3, d: Vec3) -> Vec<f64> {
+        let r2 = self.major_radius * self.major_radius;
+        let n = Vec3::dot(o, d);
+        let k = o.length_squared() + r2 - self.minor_radius * self.minor_radius;
+        vec![
+            k * k - 4.0 * r2 * (o.x * o.x + o.z * o.z),
+            4.0 * n * k - 8.0 * r2 * (o.x * d.x + o.z * d.z),
+            4.0 * n * n + 2.0 * k - 4.0 * r2 * (d.x * d.x + d.z * d.z),
+            4.0 * n,
+            1.0,
+        ]
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let o = ray.origin - self.center;
+        let d: Vec3 = ray.direction.into();
+        let range = bounds(o, d, self.major_radius + self.minor_radius, t_min, t_max)?;
+        let t = first_root(o, d, range, |start| self.coefficients(start, d))?;
+
+        let p = o + t * d;
+        let ring_distance = (p.x * p.x + p.z * p.z).sqrt();
+        let ring = if ring_distance > 0.0 {
+            (self.major_radius / ring_distance) * Vec3::new(p.x, 0.0, p.z)
+        } else {
+            Vec3::new(self.major_radius, 0.0, 0.0)
+        };
+        let normal = (p - ring).unit_vector();
+
+        let u = ((-p.z).atan2(p.x) + PI) / (2.0 * PI);
+        let v = (p.y.atan2(ring_distance - self.major_radius) + PI) / (2.0 * PI);
+
+        Some(Hit::new(t, ray, ray.at(t), normal, (u, v)))
+    }
+}
+
+impl Object for Torus {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+// An implicit surface f(x, y, z) = 0 where f is a polynomial of degree at most
+// four, given as terms `(coefficient, [i, j, k])` for c x^i y^j z^k in
+// coordinates relative to `center`. Only the part of the surface inside the
+// sphere of `bound_radius` around the center is rendered.
+pub struct QuarticSurface {
+    pub center: Point3,
+    pub terms: Vec<(f64, [u32; 3])>,
+    pub bound_radius: f64,
+    pub material: Material,
+}
+
+fn power(base: [f64; 2], exponent: u32) -> Vec<f64> {
+    (0..exponent).fold(vec![1.0], |acc, _| multiply(&acc, &base))
+}
+
+impl QuarticSurface {
+    pub fn new(
+        center: Point3,
+        terms: Vec<(f64, [u32; 3])>,
+        bound_radius: f64,
+        material: &Material,
+    ) -> Self {
+        assert!(
+            terms.iter().all(|(_, [i, j, k])| i + j + k <= 4),
+            "quartic surfaces have terms of degree at most four"
+        );
+        QuarticSurface {
+            center,
+            terms,
+            bound_radius,
+            material: material.clone(),
+        }
+    }
+
+    fn coefficients(&self, o: Vec3, d: Vec3) -> Vec<f64> {
+        let mut result = vec![0.0; 5];
+        for &(c, [i, j, k]) in &self.terms {
+            let term = multiply(
+                &multiply(&power([o.x, d.x], i), &power([o.y, d.y], j)),
+                &power([o.z, d.z], k),
+            );
+            for (degree, value) in term.iter().enumerate() {
+                result[degree] += c * value;
+            }
+        }
+        result
+    }
+
+    fn gradient(&self, p: Vec3) -> Vec3 {
+        let derivative = |x: f64, n: u32| {
+            if n == 0 {
+                0.0
+            } else {
+                n as f64 * x.powi(n as i32 - 1)
+            }
+        };
+        self.terms
+            .iter()
+            .map(|&(c, [i, j, k])| {
+                let (x, y, z) = (p.x.powi(i as i32), p.y.powi(j as i32), p.z.powi(k as i32));
+                c * Vec3::new(
+                    derivative(p.x, i) * y * z,
+                    x * derivative(p.y, j) * z,
+                    x * y * derivative(p.z, k),
+                )
+            })
+            .sum()
+    }
+}
+
+impl Hittable for QuarticSurface {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let o = ray.origin - self.center;
+        let d: Vec3 = ray.direction.into();
+        let range = bounds(o, d, self.bound_radius, t_min, t_max)?;
+        let t = first_root(o, d, range, |start| self.coefficients(start, d))?;
+
+        let p = o + t * d;
+        let normal = self.gradient(p);
+        if normal.near_zero() {
+            return None;
+        }
+        let direction = p / self.bound_radius;
+        let u = ((-direction.z).atan2(direction.x) + PI) / (2.0 * PI);
+        let v = (-direction.y).clamp(-1.0, 1.0).acos() / PI;
+
+        Some(Hit::new(t, ray, ray.at(t), normal.unit_vector(), (u, v)))
+    }
+}
+
+impl Object for QuarticSurface {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
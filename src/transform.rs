@@ -0,0 +1,175 @@
+use super::animation::{Interpolate, Keyframes};
+use super::camera::Angle;
+use super::hittable::{Hit, Hittable};
+use super::material::Material;
+use super::matrix::Matrix4;
+use super::vec3::{Ray, Vec3};
+use super::world::Object;
+use std::sync::Arc;
+
+// An object placed in the world by an affine transform. Rays are moved into
+// the object's space to be intersected, and hits moved back out. The inner
+// object is shared, so many instances of one object cost little memory.
+pub struct Transformed<H: ?Sized> {
+    inner: Arc<H>,
+    object_to_world: Matrix4,
+    world_to_object: Matrix4,
+    normal_matrix: Matrix4,
+}
+
+impl<H: ?Sized> Transformed<H> {
+    pub fn new(inner: Arc<H>, object_to_world: Matrix4) -> Self {
+        let world_to_object = object_to_world
+            .inverse()
+            .expect("object transform must be invertible");
+        Transformed {
+            inner,
+            object_to_world,
+            world_to_object,
+            normal_matrix: world_to_object.transpose(),
+        }
+    }
+}
+
+// Intersect `inner` with a ray transformed by `world_to_object`, and move the
+// hit back to world space.
+fn transformed_hit<H: Hittable + ?Sized>(
+    inner: &H,
+    object_to_world: &Matrix4,
+    world_to_object: &Matrix4,
+    normal_matrix: &Matrix4,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<Hit> {
+    let direction = world_to_object.transform_vector(ray.direction.into());
+    let scale = direction.length();
+    let object_ray = ray.spawn(
+        world_to_object.transform_point(ray.origin),
+        direction.unit_vector(),
+    );
+
+    let hit = inner.hit(&object_ray, t_min * scale, t_max * scale)?;
+    let outward_normal = hit.outward_normal();
+
+    let t = hit.t / scale;
+    Some(Hit::new(
+        t,
+        ray,
+        object_to_world.transform_point(hit.point),
+        normal_matrix
+            .transform_vector(outward_normal.into())
+            .unit_vector(),
+        (hit.u, hit.v),
+    ))
+}
+
+impl<H: Hittable + ?Sized> Hittable for Transformed<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        transformed_hit(
+            self.inner.as_ref(),
+            &self.object_to_world,
+            &self.world_to_object,
+            &self.normal_matrix,
+            ray,
+            t_min,
+            t_max,
+        )
+    }
+}
+
+impl<H: Object + ?Sized> Object for Transformed<H> {
+    fn material(&self) -> &Material {
+        self.inner.material()
+    }
+}
+
+// A placement of an object: scaled, then rotated about the x, y and z axes in
+// turn by the angles in `rotation` (in degrees), then translated.
+#[derive(Copy, Clone)]
+pub struct Pose {
+    pub translation: Vec3,
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Pose {
+    pub fn new(translation: Vec3) -> Self {
+        Pose {
+            translation,
+            rotation: Vec3::new(0.0, 0.0, 0.0),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn matrix(&self) -> Matrix4 {
+        let r = self.rotation;
+        Matrix4::translation(self.translation)
+            * Matrix4::rotation(Vec3::new(0.0, 0.0, 1.0), Angle::from_degrees(r.z))
+            * Matrix4::rotation(Vec3::new(0.0, 1.0, 0.0), Angle::from_degrees(r.y))
+            * Matrix4::rotation(Vec3::new(1.0, 0.0, 0.0), Angle::from_degrees(r.x))
+            * Matrix4::scaling(self.scale)
+    }
+
+    // The inverse of `matrix`, built by undoing each step in reverse order.
+    pub fn inverse_matrix(&self) -> Matrix4 {
+        let r = self.rotation;
+        let s = self.scale;
+        Matrix4::scaling(Vec3::new(1.0 / s.x, 1.0 / s.y, 1.0 / s.z))
+            * Matrix4::rotation(Vec3::new(1.0, 0.0, 0.0), Angle::from_degrees(-r.x))
+            * Matrix4::rotation(Vec3::new(0.0, 1.0, 0.0), Angle::from_degrees(-r.y))
+            * Matrix4::rotation(Vec3::new(0.0, 0.0, 1.0), Angle::from_degrees(-r.z))
+            * Matrix4::translation(-self.translation)
+    }
+}
+
+// Poses are blended component by component, so rotations are interpolated as
+// Euler angles. That is smooth for turns about one axis, but large turns about
+// several axes take a roundabout path, especially near gimbal lock, where
+// keyframes need to be placed closer together.
+
+impl Interpolate for Pose {
+    fn weighted_sum(terms: &[(f64, Self)]) -> Self {
+        let sum = |part: fn(&Pose) -> Vec3| terms.iter().map(|(w, pose)| *w * part(pose)).sum();
+        Pose {
+            translation: sum(|pose| pose.translation),
+            rotation: sum(|pose| pose.rotation),
+            scale: sum(|pose| pose.scale),
+        }
+    }
+}
+
+// An object moving through keyframed poses, placed at each ray's time.
+pub struct Keyframed<H: ?Sized> {
+    inner: Arc<H>,
+    poses: Keyframes<Pose>,
+}
+
+impl<H: ?Sized> Keyframed<H> {
+    pub fn new(inner: Arc<H>, poses: Keyframes<Pose>) -> Self {
+        Keyframed { inner, poses }
+    }
+}
+
+impl<H: Hittable + ?Sized> Hittable for Keyframed<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        let pose = self.poses.at(ray.time);
+        let object_to_world = pose.matrix();
+        let world_to_object = pose.inverse_matrix();
+        transformed_hit(
+            self.inner.as_ref(),
+            &object_to_world,
+            &world_to_object,
+            &world_to_object.transpose(),
+            ray,
+            t_min,
+            t_max,
+        )
+    }
+}
+
+impl<H: Object + ?Sized> Object for Keyframed<H> {
+    fn material(&self) -> &Material {
+        self.inner.material()
+    }
+}
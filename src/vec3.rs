@@ -175,14 +175,42 @@ impl Sum<Vec3> for Vec3 {
     }
 }
 
+#[derive(Copy, Clone)]
 pub struct Ray {
     pub origin: Point3,
     pub direction: UnitVec3,
+    pub wavelength: Option<f64>,
+    pub time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: UnitVec3) -> Self {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            wavelength: None,
+            time: 0.0,
+        }
+    }
+
+    pub fn with_wavelength(self, wavelength: f64) -> Self {
+        Ray {
+            wavelength: Some(wavelength),
+            ..self
+        }
+    }
+
+    pub fn with_time(self, time: f64) -> Self {
+        Ray { time, ..self }
+    }
+
+    // A new ray continuing this path, carrying its wavelength and time.
+    pub fn spawn(&self, origin: Point3, direction: UnitVec3) -> Self {
+        Ray {
+            origin,
+            direction,
+            ..*self
+        }
     }
 
     pub fn at(&self, t: f64) -> Point3 {
@@ -227,3 +255,26 @@ impl CrossProduct<Vec3> for UnitVec3 {
         self.0.cross(rhs)
     }
 }
+
+pub struct Onb {
+    pub u: UnitVec3,
+    pub v: UnitVec3,
+    pub w: UnitVec3,
+}
+
+impl Onb {
+    pub fn from_w(w: UnitVec3) -> Self {
+        let a = if w.0.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let v = w.cross(a).unit_vector();
+        let u = w.cross(v);
+        Onb { u, v, w }
+    }
+
+    pub fn local(&self, a: Vec3) -> Vec3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+}
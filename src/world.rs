@@ -1,6 +1,9 @@
-use super::hittable::{Hit, Hittable};
+use super::hittable::{Crossing, Hit, Hittable, Interval, Solid};
 use super::material::Material;
-use super::vec3::{Point3, Ray, UnitVec3, Vec3};
+use super::medium::{Medium, MediumSample, Volume};
+use super::vec3::{Color, Point3, Ray, UnitVec3, Vec3};
+use rand::{rngs::SmallRng, Rng};
+use std::f64::consts::PI;
 
 pub struct Sphere {
     pub center: Point3,
@@ -20,54 +23,232 @@ impl Sphere {
     fn outward_normal(&self, point: Point3) -> UnitVec3 {
         (point - self.center).unit_vector()
     }
+
+    fn uv(normal: UnitVec3) -> (f64, f64) {
+        let p: Vec3 = normal.into();
+        let theta = (-p.y).acos();
+        let phi = (-p.z).atan2(p.x) + PI;
+        (phi / (2.0 * PI), theta / PI)
+    }
+}
+
+fn hit_sphere(center: Point3, radius: f64, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+    let oc = ray.origin - center;
+    let half_b = Vec3::dot(oc, ray.direction.into());
+    let c = oc.length_squared() - radius * radius;
+
+    let discriminant = half_b * half_b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let sqrtd = discriminant.sqrt();
+
+    let mut root = -half_b - sqrtd;
+
+    if root < t_min || root > t_max {
+        root = -half_b + sqrtd;
+        if root < t_min || root > t_max {
+            return None;
+        }
+    }
+
+    let point = ray.at(root);
+    let normal = (point - center).unit_vector();
+
+    Some(Hit::new(root, ray, point, normal, Sphere::uv(normal)))
 }
 
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
-        let center = self.center;
-        let radius = self.radius;
+        hit_sphere(self.center, self.radius, ray, t_min, t_max)
+    }
+}
 
-        let oc = ray.origin - center;
+impl Solid for Sphere {
+    fn intervals(&self, ray: &Ray) -> Vec<Interval> {
+        let oc = ray.origin - self.center;
         let half_b = Vec3::dot(oc, ray.direction.into());
-        let c = oc.length_squared() - radius * radius;
+        let c = oc.length_squared() - self.radius * self.radius;
 
         let discriminant = half_b * half_b - c;
-        if discriminant < 0.0 {
-            return None;
+        if discriminant <= 0.0 {
+            return vec![];
         }
         let sqrtd = discriminant.sqrt();
 
-        let mut root = -half_b - sqrtd;
-
-        if root < t_min || root > t_max {
-            root = -half_b + sqrtd;
-            if root < t_min || root > t_max {
-                return None;
+        let crossing = |t: f64| {
+            let point = ray.at(t);
+            let outward_normal = self.outward_normal(point);
+            let (u, v) = Sphere::uv(outward_normal);
+            Crossing {
+                t,
+                point,
+                outward_normal,
+                u,
+                v,
             }
+        };
+        vec![Interval {
+            enter: crossing(-half_b - sqrtd),
+            exit: crossing(-half_b + sqrtd),
+        }]
+    }
+}
+
+impl Object for Sphere {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+// A sphere moving in a straight line, at `center0` at `time0` and at
+// `center1` at `time1`. Its position is extrapolated outside that range.
+pub struct MovingSphere {
+    pub center0: Point3,
+    pub center1: Point3,
+    pub time0: f64,
+    pub time1: f64,
+    pub radius: f64,
+    pub material: Material,
+}
+
+impl MovingSphere {
+    pub fn new(
+        (center0, time0): (Point3, f64),
+        (center1, time1): (Point3, f64),
+        radius: f64,
+        material: &Material,
+    ) -> Self {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material: material.clone(),
         }
+    }
 
-        let point = ray.at(root);
+    pub fn center(&self, time: f64) -> Point3 {
+        let s = (time - self.time0) / (self.time1 - self.time0);
+        self.center0 + s * (self.center1 - self.center0)
+    }
+}
 
-        Some(Hit::new(root, ray, point, self.outward_normal(point)))
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<Hit> {
+        hit_sphere(self.center(ray.time), self.radius, ray, t_min, t_max)
     }
 }
 
-pub struct World(Vec<Sphere>);
+impl Object for MovingSphere {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+}
+
+// Something in the world: a shape with a material.
+pub trait Object: Hittable + Send + Sync {
+    fn material(&self) -> &Material;
+}
+
+pub struct World {
+    objects: Vec<Box<dyn Object>>,
+    volumes: Vec<Volume>,
+    atmosphere: Option<Box<dyn Medium>>,
+}
 
 impl World {
-    pub fn new(spheres: Vec<Sphere>) -> Self {
-        World(spheres)
+    pub fn new(objects: Vec<Box<dyn Object>>) -> Self {
+        World {
+            objects,
+            volumes: vec![],
+            atmosphere: None,
+        }
     }
 
-    pub fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(Hit, &'a Sphere)> {
+    pub fn with_volume(mut self, volume: Volume) -> Self {
+        self.volumes.push(volume);
+        self
+    }
+
+    // A medium filling all of space, such as fog or haze.
+    pub fn with_atmosphere<M: Medium + 'static>(mut self, medium: M) -> Self {
+        self.atmosphere = Some(Box::new(medium));
+        self
+    }
+
+    pub fn is_dispersive(&self) -> bool {
+        self.objects
+            .iter()
+            .any(|object| object.material().is_dispersive())
+    }
+
+    pub fn hit<'a>(&'a self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(Hit, &'a dyn Object)> {
         let mut closest_so_far = t_max;
-        let mut best_so_far: Option<(Hit, &Sphere)> = None;
-        for sphere in &self.0 {
-            if let Some(hit) = sphere.hit(ray, t_min, closest_so_far) {
+        let mut best_so_far: Option<(Hit, &dyn Object)> = None;
+        for object in &self.objects {
+            if let Some(hit) = object.hit(ray, t_min, closest_so_far) {
                 closest_so_far = hit.t;
-                best_so_far = Some((hit, sphere));
+                best_so_far = Some((hit, object.as_ref()));
             }
         }
         best_so_far
     }
+
+    // Sample the media along the ray up to the surface at `t_max`. Media can
+    // overlap, as the atmosphere does every volume, so one medium is picked at
+    // random to sample a free flight, and the others attenuate the light by
+    // their transmittance up to where the flight ends. Scaling scattering by
+    // the number of media makes up for the times each isn't picked.
+    pub fn sample_media(&self, rng: &mut SmallRng, ray: &Ray, t_max: f64) -> MediumSample {
+        let mut segments: Vec<(f64, f64, &dyn Medium)> = self
+            .volumes
+            .iter()
+            .filter_map(|volume| {
+                volume
+                    .segment(ray, 0.0, t_max)
+                    .map(|(t0, t1)| (t0, t1, volume.medium.as_ref()))
+            })
+            .collect();
+        if let Some(atmosphere) = &self.atmosphere {
+            segments.push((0.0, t_max, atmosphere.as_ref()));
+        }
+        if segments.is_empty() {
+            return MediumSample::Pass(Color::new(1.0, 1.0, 1.0));
+        }
+
+        let chosen = rng.gen_range(0, segments.len());
+        let (t0, t1, medium) = segments[chosen];
+        let sample = medium.sample(rng, ray, t0, t1);
+        let end = match sample {
+            MediumSample::Scatter { t, .. } => t,
+            MediumSample::Pass(_) => t_max,
+        };
+
+        let mut others = Color::new(1.0, 1.0, 1.0);
+        for (i, &(t0, t1, medium)) in segments.iter().enumerate() {
+            if i != chosen && t0 < end {
+                others = others * medium.transmittance(rng, ray, t0, t1.min(end));
+            }
+        }
+
+        match sample {
+            MediumSample::Scatter {
+                t,
+                weight,
+                emission,
+                phase,
+            } => {
+                let scale = segments.len() as f64 * others;
+                MediumSample::Scatter {
+                    t,
+                    weight: scale * weight,
+                    emission: scale * emission,
+                    phase,
+                }
+            }
+            MediumSample::Pass(weight) => MediumSample::Pass(others * weight),
+        }
+    }
 }